@@ -22,9 +22,8 @@ fn main() -> anyhow::Result<()> {
     std::thread::sleep(Duration::from_secs(3));
 
     loop {
+        // the driver waits for each conversion to finish (about 220 ms), so
+        // you won't get double readings even without sleeping here!
         tracing::info!("Read Celsius! Got: {}° C.", max.read_celsius()?);
-
-        // you might want to sleep so you don't get double readings. feel free to play with this, though!
-        std::thread::sleep(Duration::from_millis(500));
     }
 }
@@ -0,0 +1,235 @@
+//! # array
+//!
+//! A bunch of MAX6675s, read together.
+//!
+//! Rigs often have several MAX6675s sharing SCK and SO, each with its own
+//! chip select. On Linux, that usually means one spidev per chip, like
+//! `/dev/spidev0.0`, `/dev/spidev0.1`, and so on.
+
+use std::time::Duration;
+
+use embedded_hal::spi::SpiDevice;
+
+use crate::{Error, Max6675, Temperature};
+
+/// Several MAX6675s, each on its own channel.
+///
+/// Each chip keeps track of its own conversion time, and [`Max6675Array::read_all`]
+/// reads them in whatever order they become ready. A problem with one chip
+/// (like an open thermocouple) only shows up in that chip's result, so the
+/// rest of the scan still goes through.
+///
+/// ## Example
+///
+/// ```no_run
+///
+/// use linux_max6675::{Max6675, Max6675Array};
+///
+/// let mut array: Max6675Array<_> = (0..4)
+///     .map(|cs| Max6675::new(format!("/dev/spidev0.{cs}")).unwrap())
+///     .collect();
+///
+/// for (channel, result) in array.read_all_celsius().into_iter().enumerate() {
+///     match result {
+///         Ok(celsius) => println!("channel {channel}: {celsius}° C"),
+///         Err(e) => println!("channel {channel}: {e}"),
+///     }
+/// }
+///
+/// ```
+#[derive(Debug)]
+pub struct Max6675Array<SPI> {
+    channels: Vec<Max6675<SPI>>,
+}
+
+impl<SPI> Default for Max6675Array<SPI> {
+    fn default() -> Self {
+        Self {
+            channels: Vec::new(),
+        }
+    }
+}
+
+impl<SPI> Max6675Array<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    /// Creates an array with no channels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a chip, returning its channel number.
+    pub fn push(&mut self, device: Max6675<SPI>) -> usize {
+        self.channels.push(device);
+        self.channels.len() - 1
+    }
+
+    /// How many channels there are.
+    pub fn len(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Whether there are no channels at all.
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// The chip on the given channel.
+    pub fn channel(&self, channel: usize) -> Option<&Max6675<SPI>> {
+        self.channels.get(channel)
+    }
+
+    /// The chip on the given channel, mutably.
+    pub fn channel_mut(&mut self, channel: usize) -> Option<&mut Max6675<SPI>> {
+        self.channels.get_mut(channel)
+    }
+
+    /// Gives back all the chips, in channel order.
+    pub fn into_inner(self) -> Vec<Max6675<SPI>> {
+        self.channels
+    }
+
+    /// Reads every channel, returning one result per channel.
+    ///
+    /// Like [`Max6675::read_temperature`], these are straight from the chips,
+    /// without any channel's calibration or linearization. Use
+    /// [`Max6675Array::read_all_celsius`] for corrected readings.
+    ///
+    /// Chips are read as soon as their own conversions finish, so this only
+    /// waits as long as the slowest chip needs.
+    pub fn read_all(&mut self) -> Vec<Result<Temperature, Error>> {
+        let mut order: Vec<usize> = (0..self.channels.len()).collect();
+        order.sort_by_key(|&channel| self.channels[channel].time_until_ready());
+
+        let mut results: Vec<(usize, Result<Temperature, Error>)> = order
+            .into_iter()
+            .map(|channel| {
+                let device = &mut self.channels[channel];
+
+                // wait it out here, no matter the chip's own `ConversionPolicy`
+                let remaining = device.time_until_ready();
+                if remaining > Duration::ZERO {
+                    std::thread::sleep(remaining);
+                }
+
+                (channel, device.read_temperature())
+            })
+            .collect();
+
+        // back into channel order
+        results.sort_by_key(|(channel, _)| *channel);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+
+    /// Reads every channel in Celsius, corrected by each channel's own
+    /// linearization and calibration. See [`Max6675Array::read_all`].
+    pub fn read_all_celsius(&mut self) -> Vec<Result<f64, Error>> {
+        self.read_all()
+            .into_iter()
+            .zip(&self.channels)
+            .map(|(result, device)| result.map(|temp| device.corrected_celsius(temp)))
+            .collect()
+    }
+}
+
+impl<SPI> FromIterator<Max6675<SPI>> for Max6675Array<SPI> {
+    fn from_iter<I: IntoIterator<Item = Max6675<SPI>>>(iter: I) -> Self {
+        Self {
+            channels: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{backend::mock::MockMax6675, Calibration};
+
+    fn device(setup: impl FnOnce(&mut MockMax6675)) -> Max6675<MockMax6675> {
+        let mut mock = MockMax6675::new();
+        setup(&mut mock);
+        let mut max = Max6675::from_spi(mock);
+        max.set_conversion_time(Duration::from_millis(30));
+        max
+    }
+
+    #[test]
+    fn one_bad_channel_doesnt_spoil_the_scan() {
+        let mut array: Max6675Array<_> = [
+            device(|m| {
+                m.push_celsius(20.0);
+            }),
+            device(|m| {
+                m.push_open_circuit();
+            }),
+            device(|_| ()),
+            device(|m| {
+                m.push_celsius(400.0);
+            }),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            array.read_all_celsius(),
+            vec![
+                Ok(20.0),
+                Err(Error::OpenCircuit),
+                Err(Error::ReceivedNothing),
+                Ok(400.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn channels_keep_their_own_calibration() {
+        let mut array: Max6675Array<_> = [
+            device(|m| {
+                m.push_celsius(20.0);
+            }),
+            device(|m| {
+                m.push_celsius(20.0);
+            }),
+        ]
+        .into_iter()
+        .collect();
+        array
+            .channel_mut(1)
+            .unwrap()
+            .set_calibration(Some(Calibration::new().with_offset(-1.5)));
+
+        assert_eq!(array.read_all_celsius(), vec![Ok(20.0), Ok(18.5)]);
+    }
+
+    #[test]
+    fn each_chip_gets_its_conversion_time() {
+        let mut array = Max6675Array::new();
+        for _ in 0..3 {
+            let mut max = device(|m| {
+                m.push_profile([1.0, 2.0]);
+            });
+            // long enough that waiting for each chip in turn would be obvious
+            max.set_conversion_time(Duration::from_millis(200));
+            array.push(max);
+        }
+
+        assert_eq!(array.read_all_celsius(), vec![Ok(1.0); 3]);
+
+        let start = Instant::now();
+        assert_eq!(array.read_all_celsius(), vec![Ok(2.0); 3]);
+        let elapsed = start.elapsed();
+
+        // everyone converts at the same time, so we only wait once, not
+        // three times over
+        assert!(elapsed >= Duration::from_millis(190));
+        assert!(elapsed < Duration::from_millis(400), "{elapsed:?}");
+
+        for max in array.into_inner() {
+            assert_eq!(max.into_inner().reads(), 2);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! # asynch
+//!
+//! An async version of the [`Max6675`](crate::Max6675) driver, built on
+//! [`embedded_hal_async`].
+//!
+//! Instead of blocking a thread during the chip's conversion time, this one
+//! awaits a timer. If you're on tokio, the `tokio` feature gives you a
+//! spidev backend and a timer to go with it.
+
+use std::time::{Duration, Instant};
+
+use embedded_hal_async::{delay::DelayNs, spi::SpiDevice};
+
+use crate::{Error, Reading, Temperature, CONVERSION_TIME};
+
+/// An async MAX6675.
+///
+/// ## Example
+///
+/// With the `tokio` feature:
+///
+/// ```no_run
+///
+/// # #[cfg(feature = "tokio")]
+/// # async fn run() {
+/// use linux_max6675::asynch::Max6675;
+///
+/// let mut max = Max6675::new("/dev/spidev0.0").unwrap();
+///
+/// loop {
+///     // awaits the chip's conversion time, so no need to sleep!
+///     println!("it's {}° celsius in here!", max.read_celsius().await.unwrap());
+/// }
+/// # }
+///
+/// ```
+#[derive(Debug)]
+pub struct Max6675<SPI, D> {
+    spi: SPI,
+    delay: D,
+    conversion_time: Duration,
+    /// When CS last went high, kicking off a new conversion.
+    last_read: Option<Instant>,
+}
+
+#[cfg(feature = "tokio")]
+impl Max6675<crate::backend::tokio::TokioSpidev, crate::backend::tokio::TokioDelay> {
+    /// Opens the MAX6675 at the given spidev path, like `/dev/spidev0.0`.
+    ///
+    /// This uses the spidev backend and tokio's timer.
+    pub fn new(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        Ok(Self::from_spi(
+            crate::backend::tokio::TokioSpidev::open(path)?,
+            crate::backend::tokio::TokioDelay,
+        ))
+    }
+}
+
+impl<SPI, D> Max6675<SPI, D>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+    D: DelayNs,
+{
+    /// Wraps any async SPI device, using `delay` to wait for conversions.
+    ///
+    /// Make sure it's in mode 1 and clocked at 4.3 MHz or below!
+    pub fn from_spi(spi: SPI, delay: D) -> Self {
+        Self {
+            spi,
+            delay,
+            conversion_time: CONVERSION_TIME,
+            last_read: None,
+        }
+    }
+
+    /// Gives back the underlying SPI device and delay.
+    pub fn into_inner(self) -> (SPI, D) {
+        (self.spi, self.delay)
+    }
+
+    /// Changes how long we wait for a conversion. Defaults to [`CONVERSION_TIME`].
+    pub fn set_conversion_time(&mut self, conversion_time: Duration) {
+        self.conversion_time = conversion_time;
+    }
+
+    /// How long until the current conversion is done.
+    ///
+    /// This is zero if the chip is ready to read.
+    pub fn time_until_ready(&self) -> Duration {
+        self.last_read.map_or(Duration::ZERO, |last| {
+            self.conversion_time.saturating_sub(last.elapsed())
+        })
+    }
+
+    /// Tries to return the thermocouple's raw data. See [`crate::read`] for more info.
+    ///
+    /// If the chip is still converting, this waits for it first.
+    pub async fn read_raw(&mut self) -> Result<u16, Error> {
+        let remaining = self.time_until_ready();
+        if !remaining.is_zero() {
+            // round up, so we never wake up early
+            let us = remaining.as_nanos().div_ceil(1000);
+            self.delay.delay_us(us.try_into().unwrap_or(u32::MAX)).await;
+        }
+
+        let mut buf = [0_u8; 2];
+        self.spi.read(&mut buf).await.map_err(Error::from_spi)?;
+
+        // CS just went high, so a new conversion has started
+        self.last_read = Some(Instant::now());
+
+        Ok(u16::from_be_bytes(buf))
+    }
+
+    /// Tries to read and decode a whole frame from the thermocouple.
+    ///
+    /// See [`crate::Max6675::read_frame`].
+    pub async fn read_frame(&mut self) -> Result<Reading, Error> {
+        Reading::from_raw(self.read_raw().await?)
+    }
+
+    /// Tries to read the thermocouple's exact temperature.
+    pub async fn read_temperature(&mut self) -> Result<Temperature, Error> {
+        let reading = self.read_frame().await?;
+        // Check if MAX6675 terminals are open
+        if reading.is_open() {
+            return Err(Error::OpenCircuit);
+        }
+        Ok(reading.temperature())
+    }
+
+    /// Tries to read the thermocouple's temperature in Celsius.
+    pub async fn read_celsius(&mut self) -> Result<f64, Error> {
+        Ok(self.read_temperature().await?.celsius())
+    }
+
+    /// Tries to read the thermocouple's temperature in Fahrenheit.
+    pub async fn read_fahrenheit(&mut self) -> Result<f64, Error> {
+        Ok(self.read_temperature().await?.fahrenheit())
+    }
+
+    /// Tries to read the thermocouple's temperature in Kelvin.
+    pub async fn read_kelvin(&mut self) -> Result<f64, Error> {
+        Ok(self.read_temperature().await?.kelvin())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockMax6675;
+
+    /// A delay that just remembers how long it was asked to wait.
+    #[derive(Debug, Default)]
+    struct RecordingDelay {
+        waited_ns: u64,
+    }
+
+    impl DelayNs for RecordingDelay {
+        async fn delay_ns(&mut self, ns: u32) {
+            self.waited_ns += u64::from(ns);
+        }
+    }
+
+    #[tokio::test]
+    async fn awaits_the_conversion_window() {
+        let mut mock = MockMax6675::new();
+        mock.push_profile([25.0, 25.25]);
+        let mut max = Max6675::from_spi(mock, RecordingDelay::default());
+
+        assert_eq!(max.read_celsius().await, Ok(25.0));
+        assert_eq!(max.read_celsius().await, Ok(25.25));
+
+        let (_, delay) = max.into_inner();
+        let waited = Duration::from_nanos(delay.waited_ns);
+        assert!(waited > CONVERSION_TIME - Duration::from_millis(50));
+        assert!(waited <= CONVERSION_TIME);
+    }
+
+    #[tokio::test]
+    async fn reports_errors() {
+        let mut mock = MockMax6675::new();
+        mock.push_open_circuit().push_frame(0x8000);
+        let mut max = Max6675::from_spi(mock, RecordingDelay::default());
+        max.set_conversion_time(Duration::ZERO);
+
+        assert_eq!(max.read_celsius().await, Err(Error::OpenCircuit));
+        assert_eq!(
+            max.read_celsius().await,
+            Err(Error::InvalidFrame { raw: 0x8000 })
+        );
+        assert_eq!(max.read_celsius().await, Err(Error::ReceivedNothing));
+    }
+}
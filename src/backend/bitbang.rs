@@ -0,0 +1,403 @@
+//! # bitbang
+//!
+//! A bit-banged SPI backend, for boards without a free SPI controller.
+//!
+//! The MAX6675 only ever talks (it has no data input pin), so all we need are
+//! three GPIOs: CS and SCK as outputs, and SO as an input. Any
+//! [`embedded_hal::digital`] pins will do. With the `gpio` feature, you can
+//! use Linux's GPIO character devices (`/dev/gpiochipN`) directly.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use embedded_hal::{
+    digital::{InputPin, OutputPin},
+    spi::{ErrorKind, ErrorType, Operation, SpiDevice},
+};
+
+use crate::Error;
+
+/// The default SCK period: 100 kHz, which is plenty for one 16-bit word.
+pub const DEFAULT_CLOCK_PERIOD: Duration = Duration::from_micros(10);
+
+/// Turns a GPIO error into an SPI [`Error`].
+fn pin_error(e: impl fmt::Debug) -> Error {
+    Error::SPI {
+        kind: ErrorKind::Other,
+        message: format!("GPIO error while bit-banging: {e:?}"),
+    }
+}
+
+/// Waits for a (usually very short) while.
+///
+/// Sleeping the thread is far too coarse for microsecond delays, so short
+/// waits just spin.
+fn wait(duration: Duration) {
+    if duration >= Duration::from_millis(1) {
+        std::thread::sleep(duration);
+    } else {
+        let start = Instant::now();
+        while start.elapsed() < duration {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+/// SPI, bit-banged over three GPIO pins.
+///
+/// This produces the same 16-bit word that [`crate::read`] does over hardware
+/// SPI, so everything else works unchanged.
+///
+/// ## Example
+///
+/// With the `gpio` feature:
+///
+/// ```no_run
+///
+/// # #[cfg(feature = "gpio")]
+/// # {
+/// use linux_max6675::{backend::bitbang::BitBangSpi, Max6675};
+///
+/// // CS on line 17, SCK on line 27, SO on line 22
+/// let spi = BitBangSpi::open("/dev/gpiochip0", 17, 27, 22).unwrap();
+/// let mut max = Max6675::from_spi(spi);
+///
+/// println!("it's {}° celsius in here!", max.read_celsius().unwrap());
+/// # }
+///
+/// ```
+#[derive(Debug)]
+pub struct BitBangSpi<CS, SCK, SO> {
+    cs: CS,
+    sck: SCK,
+    so: SO,
+    clock_period: Duration,
+}
+
+impl<CS, SCK, SO> BitBangSpi<CS, SCK, SO>
+where
+    CS: OutputPin,
+    SCK: OutputPin,
+    SO: InputPin,
+{
+    /// Bit-bangs SPI over the given pins at [`DEFAULT_CLOCK_PERIOD`].
+    ///
+    /// CS is driven high (inactive) and SCK low (idle) right away.
+    pub fn new(mut cs: CS, mut sck: SCK, so: SO) -> Result<Self, Error> {
+        cs.set_high().map_err(pin_error)?;
+        sck.set_low().map_err(pin_error)?;
+
+        Ok(Self {
+            cs,
+            sck,
+            so,
+            clock_period: DEFAULT_CLOCK_PERIOD,
+        })
+    }
+
+    /// Changes the SCK period.
+    ///
+    /// The MAX6675 needs at least 200 ns per period (see MAX6675 datasheet,
+    /// p. 3), but your GPIOs are probably slower than that anyway.
+    pub fn set_clock_period(&mut self, clock_period: Duration) {
+        self.clock_period = clock_period;
+    }
+
+    /// Gives back the pins: CS, SCK, and SO.
+    pub fn into_inner(self) -> (CS, SCK, SO) {
+        (self.cs, self.sck, self.so)
+    }
+
+    /// Clocks one byte in from SO, most significant bit first.
+    ///
+    /// The MAX6675 shifts out a new bit on each falling edge of SCK, so we
+    /// sample while SCK is low.
+    fn clock_byte(&mut self) -> Result<u8, Error> {
+        let half = self.clock_period / 2;
+        let mut byte = 0_u8;
+
+        for _ in 0..8 {
+            self.sck.set_low().map_err(pin_error)?;
+            wait(half);
+            byte = (byte << 1) | u8::from(self.so.is_high().map_err(pin_error)?);
+            self.sck.set_high().map_err(pin_error)?;
+            wait(half);
+        }
+
+        Ok(byte)
+    }
+
+    /// Runs the operations with CS held low.
+    fn run(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        for op in operations {
+            match op {
+                Operation::Read(buf)
+                | Operation::Transfer(buf, _)
+                | Operation::TransferInPlace(buf) => {
+                    for byte in buf.iter_mut() {
+                        *byte = self.clock_byte()?;
+                    }
+                }
+                // there's no data pin going *to* the chip, so just clock
+                Operation::Write(buf) => {
+                    for _ in 0..buf.len() {
+                        self.clock_byte()?;
+                    }
+                }
+                Operation::DelayNs(ns) => wait(Duration::from_nanos(u64::from(*ns))),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<CS, SCK, SO> ErrorType for BitBangSpi<CS, SCK, SO> {
+    type Error = Error;
+}
+
+impl<CS, SCK, SO> SpiDevice for BitBangSpi<CS, SCK, SO>
+where
+    CS: OutputPin,
+    SCK: OutputPin,
+    SO: InputPin,
+{
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        self.cs.set_low().map_err(pin_error)?;
+        // give the chip a moment to put D15 on SO
+        wait(self.clock_period / 2);
+
+        let result = self.run(operations);
+
+        // back to idle. CS going high starts the next conversion
+        let sck = self.sck.set_low().map_err(pin_error);
+        let cs = self.cs.set_high().map_err(pin_error);
+
+        result.and(sck).and(cs)
+    }
+}
+
+#[cfg(feature = "gpio")]
+mod gpio {
+    use std::path::Path;
+
+    use gpio_cdev::{Chip, LineHandle, LineRequestFlags};
+
+    use super::{BitBangSpi, ErrorKind};
+    use crate::Error;
+
+    /// What we call ourselves when asking the kernel for GPIO lines.
+    const CONSUMER: &str = "linux_max6675";
+
+    fn gpio_error(doing: &str, e: gpio_cdev::Error) -> Error {
+        Error::SPI {
+            kind: ErrorKind::Other,
+            message: format!("Failed to {doing}: {e}"),
+        }
+    }
+
+    /// A GPIO line from a Linux GPIO character device.
+    #[derive(Debug)]
+    pub struct CdevPin {
+        handle: LineHandle,
+    }
+
+    impl CdevPin {
+        /// Requests a line from the chip.
+        ///
+        /// `default` is the line's starting value, if it's an output.
+        fn request(
+            chip: &mut Chip,
+            offset: u32,
+            flags: LineRequestFlags,
+            default: u8,
+        ) -> Result<Self, Error> {
+            let line = chip
+                .get_line(offset)
+                .map_err(|e| gpio_error(&format!("get GPIO line {offset}"), e))?;
+
+            let handle = line
+                .request(flags, default, CONSUMER)
+                .map_err(|e| gpio_error(&format!("request GPIO line {offset}"), e))?;
+
+            Ok(Self { handle })
+        }
+    }
+
+    /// An error from a [`CdevPin`].
+    #[derive(Debug)]
+    pub struct CdevPinError(pub gpio_cdev::Error);
+
+    impl embedded_hal::digital::Error for CdevPinError {
+        fn kind(&self) -> embedded_hal::digital::ErrorKind {
+            embedded_hal::digital::ErrorKind::Other
+        }
+    }
+
+    impl embedded_hal::digital::ErrorType for CdevPin {
+        type Error = CdevPinError;
+    }
+
+    impl embedded_hal::digital::OutputPin for CdevPin {
+        fn set_low(&mut self) -> Result<(), CdevPinError> {
+            self.handle.set_value(0).map_err(CdevPinError)
+        }
+
+        fn set_high(&mut self) -> Result<(), CdevPinError> {
+            self.handle.set_value(1).map_err(CdevPinError)
+        }
+    }
+
+    impl embedded_hal::digital::InputPin for CdevPin {
+        fn is_high(&mut self) -> Result<bool, CdevPinError> {
+            Ok(self.handle.get_value().map_err(CdevPinError)? != 0)
+        }
+
+        fn is_low(&mut self) -> Result<bool, CdevPinError> {
+            Ok(!self.is_high()?)
+        }
+    }
+
+    impl BitBangSpi<CdevPin, CdevPin, CdevPin> {
+        /// Bit-bangs SPI over lines of a GPIO chip, like `/dev/gpiochip0`.
+        ///
+        /// `cs`, `sck` and `so` are line offsets on that chip.
+        pub fn open(chip: impl AsRef<Path>, cs: u32, sck: u32, so: u32) -> Result<Self, Error> {
+            let path = chip.as_ref();
+            let mut chip = Chip::new(path)
+                .map_err(|e| gpio_error(&format!("open `{}`", path.display()), e))?;
+
+            // CS starts high and SCK starts low, so the chip stays idle
+            Self::new(
+                CdevPin::request(&mut chip, cs, LineRequestFlags::OUTPUT, 1)?,
+                CdevPin::request(&mut chip, sck, LineRequestFlags::OUTPUT, 0)?,
+                CdevPin::request(&mut chip, so, LineRequestFlags::INPUT, 0)?,
+            )
+        }
+    }
+}
+
+#[cfg(feature = "gpio")]
+pub use gpio::{CdevPin, CdevPinError};
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, convert::Infallible, rc::Rc};
+
+    use embedded_hal::digital::ErrorType as PinErrorType;
+
+    use super::*;
+
+    /// A pretend MAX6675 shift register, driven by our pretend pins.
+    #[derive(Debug, Default)]
+    struct Chip {
+        word: u16,
+        cs_low: bool,
+        sck_high: bool,
+        bit: u32,
+        conversions: usize,
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    enum Role {
+        Cs,
+        Sck,
+        So,
+    }
+
+    #[derive(Debug)]
+    struct Pin(Rc<RefCell<Chip>>, Role);
+
+    impl PinErrorType for Pin {
+        type Error = Infallible;
+    }
+
+    impl Pin {
+        fn set(&mut self, high: bool) {
+            let mut chip = self.0.borrow_mut();
+            match self.1 {
+                Role::Cs => {
+                    if !high && !chip.cs_low {
+                        // D15 shows up as soon as CS falls
+                        chip.bit = 15;
+                    } else if high && chip.cs_low {
+                        chip.conversions += 1;
+                    }
+                    chip.cs_low = !high;
+                }
+                Role::Sck => {
+                    // ...and the rest come out on falling edges
+                    if !high && chip.sck_high && chip.cs_low {
+                        chip.bit = chip.bit.saturating_sub(1);
+                    }
+                    chip.sck_high = high;
+                }
+                Role::So => unreachable!("SO is an input"),
+            }
+        }
+    }
+
+    impl OutputPin for Pin {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.set(false);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.set(true);
+            Ok(())
+        }
+    }
+
+    impl InputPin for Pin {
+        fn is_high(&mut self) -> Result<bool, Infallible> {
+            let chip = self.0.borrow();
+            Ok(chip.cs_low && (chip.word >> chip.bit) & 1 == 1)
+        }
+
+        fn is_low(&mut self) -> Result<bool, Infallible> {
+            Ok(!self.is_high()?)
+        }
+    }
+
+    fn bitbang(word: u16) -> (BitBangSpi<Pin, Pin, Pin>, Rc<RefCell<Chip>>) {
+        let chip = Rc::new(RefCell::new(Chip {
+            word,
+            ..Default::default()
+        }));
+
+        let mut spi = BitBangSpi::new(
+            Pin(Rc::clone(&chip), Role::Cs),
+            Pin(Rc::clone(&chip), Role::Sck),
+            Pin(Rc::clone(&chip), Role::So),
+        )
+        .unwrap();
+        spi.set_clock_period(Duration::ZERO);
+
+        (spi, chip)
+    }
+
+    #[test]
+    fn reads_the_same_word_as_hardware_spi() {
+        for word in [0x0000, 0x0004, 0x1234, 0x7FF8, 0xA5A5, 0xFFFF] {
+            let (mut spi, chip) = bitbang(word);
+
+            assert_eq!(crate::read(&mut spi), Ok(word));
+
+            let chip = chip.borrow();
+            assert!(!chip.cs_low, "CS should be released");
+            assert!(!chip.sck_high, "SCK should idle low");
+            assert_eq!(chip.conversions, 1);
+        }
+    }
+
+    #[test]
+    fn works_with_the_driver() {
+        let (spi, _) = bitbang(0b0000_0011_0010_0000);
+        let mut max = crate::Max6675::from_spi(spi);
+
+        assert_eq!(max.read_celsius(), Ok(25.0));
+    }
+}
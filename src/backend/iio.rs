@@ -0,0 +1,610 @@
+//! # iio
+//!
+//! A backend for chips that are bound to the kernel's `maxim_thermocouple`
+//! (or `max31856`) IIO driver, instead of spidev.
+//!
+//! The kernel driver does the SPI for us, and puts the readings in sysfs:
+//!
+//! - `/sys/bus/iio/devices/iio:deviceN/name`: the chip, like `max6675`.
+//! - `in_temp_raw`: the temperature, in chip units. Reading this fails with
+//!   `EINVAL` when the thermocouple is open.
+//! - `in_temp_scale`: how many thousandths of a degree each unit is.
+//! - `in_temp_ambient_raw` and `in_temp_ambient_scale`: the chip's own
+//!   temperature, for chips that have one.
+//!
+//! [`Iio`] reads those, and also pretends to be an SPI device that sends
+//! MAX6675 frames, so you can hand it to [`Max6675::from_spi`](crate::Max6675::from_spi)
+//! and keep using the same driver API.
+//!
+//! If you've set up a trigger for the device, [`Iio::enable_buffer`] reads
+//! from its buffered character device (`/dev/iio:deviceN`) instead.
+//!
+//! ## Example
+//!
+//! ```no_run
+//!
+//! use linux_max6675::{backend::iio, Max6675};
+//!
+//! let device = iio::devices().unwrap().into_iter().next().expect("no MAX6675s bound");
+//! let mut max = Max6675::from_spi(device);
+//!
+//! println!("it's {}° C in here!", max.read_celsius().unwrap());
+//!
+//! ```
+
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use embedded_hal::spi::{ErrorKind, ErrorType, Operation, SpiDevice};
+
+use super::io_error;
+use crate::{
+    converter::{max31855, Conversion, ThermocoupleConverter},
+    Error, Sensor,
+};
+
+/// Where sysfs usually lives.
+pub const SYSFS_ROOT: &str = "/sys";
+
+/// The chips Linux has IIO drivers for, by their `name` prefix.
+const PARTS: [(&str, &str); 3] = [
+    ("max6675", "MAX6675"),
+    ("max31855", "MAX31855"),
+    ("max31856", "MAX31856"),
+];
+
+/// How a channel's samples are laid out in the buffer, from its
+/// `scan_elements/*_type` file. That looks like `be:s13/16>>3`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScanType {
+    pub big_endian: bool,
+    pub signed: bool,
+    /// How many bits are the actual value.
+    pub real_bits: u32,
+    /// How many bits each sample takes up in the buffer.
+    pub storage_bits: u32,
+    /// How far to shift the sample right to get the value.
+    pub shift: u32,
+}
+
+impl ScanType {
+    /// Parses a scan type, like `be:s13/16>>3`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (endian, rest) = s.trim().split_once(':')?;
+        let big_endian = match endian {
+            "be" => true,
+            "le" => false,
+            _ => return None,
+        };
+
+        let signed = match rest.as_bytes().first()? {
+            b's' => true,
+            b'u' => false,
+            _ => return None,
+        };
+
+        let (bits, shift) = rest[1..].split_once(">>")?;
+        let (real_bits, storage_bits) = bits.split_once('/')?;
+        // there might be a repeat count (`X2`) on the end, which we don't need
+        let storage_bits = storage_bits.split('X').next()?;
+
+        let scan = Self {
+            big_endian,
+            signed,
+            real_bits: real_bits.parse().ok()?,
+            storage_bits: storage_bits.parse().ok()?,
+            shift: shift.parse().ok()?,
+        };
+
+        let sane = matches!(scan.storage_bits, 8 | 16 | 32)
+            && (1..=scan.storage_bits).contains(&scan.real_bits)
+            && scan.shift < scan.storage_bits;
+        sane.then_some(scan)
+    }
+
+    /// Pulls the value out of a stored sample.
+    pub fn decode(&self, sample: u32) -> i32 {
+        let value = (sample >> self.shift) & (u32::MAX >> (32 - self.real_bits));
+
+        if self.signed {
+            // move the sign bit to the top, then back down again
+            let unused = 32 - self.real_bits;
+            ((value << unused) as i32) >> unused
+        } else {
+            value as i32
+        }
+    }
+}
+
+/// The buffered character device, once it's on.
+#[derive(Debug)]
+struct Buffer {
+    file: File,
+    scan: ScanType,
+}
+
+/// A thermocouple converter bound to the kernel's IIO driver.
+///
+/// As an [`SpiDevice`], every 2-byte read gives back a MAX6675 frame built
+/// from the latest reading. That's exact for MAX6675s. For other chips,
+/// the temperature is rounded to a quarter degree and clamped to the
+/// MAX6675's range, so use [`Iio::read_conversion`] for those instead.
+#[derive(Debug)]
+pub struct Iio {
+    dir: PathBuf,
+    name: String,
+    part: &'static str,
+    /// Thousandths of a degree per unit.
+    scale: f64,
+    offset: f64,
+    buffer: Option<Buffer>,
+}
+
+/// Finds every Maxim thermocouple converter bound to an IIO driver.
+pub fn devices() -> Result<Vec<Iio>, Error> {
+    devices_in(SYSFS_ROOT)
+}
+
+/// Finds every Maxim thermocouple converter bound to an IIO driver, with
+/// sysfs at `root` instead of `/sys`. Handy for testing.
+///
+/// Devices come back sorted by path, so `iio:device0` is first.
+pub fn devices_in(root: impl AsRef<Path>) -> Result<Vec<Iio>, Error> {
+    let dir = root.as_ref().join("bus/iio/devices");
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        // no IIO at all means no devices
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(io_error(&format!("list `{}`", dir.display()), e)),
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("iio:device"))
+        })
+        .collect();
+    paths.sort();
+
+    Ok(paths
+        .into_iter()
+        // anything else on the bus just isn't ours
+        .filter_map(|path| Iio::open(path).ok())
+        .collect())
+}
+
+impl Iio {
+    /// Opens an IIO device by its sysfs directory, like
+    /// `/sys/bus/iio/devices/iio:device0`.
+    ///
+    /// Fails if it isn't a Maxim thermocouple converter.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        let name = read_attribute(&dir, "name")?;
+
+        let Some(&(_, part)) = PARTS.iter().find(|(prefix, _)| name.starts_with(prefix)) else {
+            return Err(Error::SPI {
+                kind: ErrorKind::Other,
+                message: format!(
+                    "`{}` is a `{name}`, not a Maxim thermocouple converter.",
+                    dir.display()
+                ),
+            });
+        };
+
+        let scale = parse_attribute(&dir, "in_temp_scale")?;
+        let offset = if dir.join("in_temp_offset").exists() {
+            parse_attribute(&dir, "in_temp_offset")?
+        } else {
+            0.0
+        };
+
+        Ok(Self {
+            dir,
+            name,
+            part,
+            scale,
+            offset,
+            buffer: None,
+        })
+    }
+
+    /// The device's sysfs directory.
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// What the kernel calls the chip, like `max6675` or `max31855k`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Starts reading from the buffered character device at `dev`, usually
+    /// `/dev/iio:deviceN`.
+    ///
+    /// This turns on the temperature channel (and off the rest), then
+    /// enables the buffer. You'll need to have set up a trigger for the
+    /// device already, or there won't be anything to read.
+    pub fn enable_buffer(&mut self, dev: impl AsRef<Path>) -> Result<(), Error> {
+        let scan_elements = self.dir.join("scan_elements");
+        let channels = fs::read_dir(&scan_elements)
+            .map_err(|e| io_error(&format!("list `{}`", scan_elements.display()), e))?;
+
+        for channel in channels.filter_map(|entry| entry.ok()) {
+            let file_name = channel.file_name();
+            let Some(name) = file_name.to_str() else {
+                continue;
+            };
+            if name.ends_with("_en") {
+                let on = if name == "in_temp_en" { "1" } else { "0" };
+                write_attribute(&scan_elements, name, on)?;
+            }
+        }
+
+        let scan_type = read_attribute(&scan_elements, "in_temp_type")?;
+        let scan = ScanType::parse(&scan_type).ok_or_else(|| Error::SPI {
+            kind: ErrorKind::Other,
+            message: format!("Couldn't understand the scan type `{scan_type}`."),
+        })?;
+
+        write_attribute(&self.dir, "buffer/enable", "1")?;
+
+        let dev = dev.as_ref();
+        let file =
+            File::open(dev).map_err(|e| io_error(&format!("open `{}`", dev.display()), e))?;
+        self.buffer = Some(Buffer { file, scan });
+
+        Ok(())
+    }
+
+    /// Goes back to reading sysfs.
+    pub fn disable_buffer(&mut self) -> Result<(), Error> {
+        if self.buffer.take().is_some() {
+            write_attribute(&self.dir, "buffer/enable", "0")?;
+        }
+        Ok(())
+    }
+
+    /// Tries to read the raw temperature, in chip units.
+    ///
+    /// Fails with [`Error::OpenCircuit`] if the driver says the thermocouple
+    /// is open. From sysfs, the driver can't tell us which fault it saw, so
+    /// a MAX31855's shorts look like open circuits too.
+    pub fn read_raw(&mut self) -> Result<i32, Error> {
+        let Some(buffer) = &mut self.buffer else {
+            let path = self.dir.join("in_temp_raw");
+            return match fs::read_to_string(&path) {
+                Ok(raw) => raw.trim().parse().map_err(|_| Error::SPI {
+                    kind: ErrorKind::Other,
+                    message: format!(
+                        "`in_temp_raw` should be a number, but it's `{}`.",
+                        raw.trim()
+                    ),
+                }),
+                // the driver fails with `EINVAL` when there's a fault, which
+                // Rust calls `InvalidInput`
+                Err(e) if e.kind() == io::ErrorKind::InvalidInput => Err(Error::OpenCircuit),
+                Err(e) => Err(io_error(&format!("read `{}`", path.display()), e)),
+            };
+        };
+
+        let mut bytes = [0_u8; 4];
+        let len = buffer.scan.storage_bits as usize / 8;
+        let bytes = &mut bytes[..len];
+        buffer
+            .file
+            .read_exact(bytes)
+            .map_err(|e| io_error("read the IIO buffer", e))?;
+
+        let sample = bytes
+            .iter()
+            .fold(0_u32, |acc, b| (acc << 8) | u32::from(*b));
+        let sample = if buffer.scan.big_endian {
+            sample
+        } else {
+            sample.swap_bytes() >> (32 - buffer.scan.storage_bits)
+        };
+
+        // the buffer has the whole frame, so we check the fault bits ourselves
+        match self.part {
+            "MAX6675" if crate::is_open(sample as u16) => return Err(Error::OpenCircuit),
+            "MAX31855" => {
+                if let Some(e) = max31855::fault(sample) {
+                    return Err(e);
+                }
+            }
+            _ => (),
+        }
+
+        Ok(buffer.scan.decode(sample))
+    }
+
+    /// Tries to read the thermocouple's temperature in Celsius.
+    pub fn read_celsius(&mut self) -> Result<f64, Error> {
+        let raw = self.read_raw()?;
+        Ok(self.to_celsius(raw))
+    }
+
+    /// Converts chip units to Celsius.
+    fn to_celsius(&self, raw: i32) -> f64 {
+        (f64::from(raw) + self.offset) * self.scale / 1000.0
+    }
+
+    /// Tries to read the chip's own temperature in Celsius, if it has one.
+    pub fn read_cold_junction(&mut self) -> Result<Option<f64>, Error> {
+        if !self.dir.join("in_temp_ambient_raw").exists() {
+            return Ok(None);
+        }
+
+        let raw = parse_attribute(&self.dir, "in_temp_ambient_raw")?;
+        let scale = parse_attribute(&self.dir, "in_temp_ambient_scale")?;
+        Ok(Some(raw * scale / 1000.0))
+    }
+
+    /// Tries to read the thermocouple and the chip's own temperature.
+    pub fn read_conversion(&mut self) -> Result<Conversion, Error> {
+        Ok(Conversion {
+            thermocouple: self.read_celsius()?,
+            cold_junction: self.read_cold_junction()?,
+        })
+    }
+
+    /// Builds the MAX6675 frame that matches the latest reading.
+    fn frame(&mut self) -> Result<u16, Error> {
+        match self.read_raw() {
+            Ok(raw) if self.part == "MAX6675" => {
+                // the driver gives us D15-D3, which is already quarter degrees
+                Ok((raw.clamp(0, 0x0FFF) as u16) << 3)
+            }
+            Ok(raw) => {
+                let quarters = (self.to_celsius(raw) * 4.0).round().clamp(0.0, 4095.0) as u16;
+                Ok(quarters << 3)
+            }
+            // the open bit (D2)
+            Err(Error::OpenCircuit) => Ok(0x0004),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Drop for Iio {
+    fn drop(&mut self) {
+        // leaving the buffer on would keep the trigger busy for nothing
+        let _ = self.disable_buffer();
+    }
+}
+
+/// Reads a sysfs attribute, without the trailing newline.
+fn read_attribute(dir: &Path, attribute: &str) -> Result<String, Error> {
+    let path = dir.join(attribute);
+    fs::read_to_string(&path)
+        .map(|s| s.trim().to_string())
+        .map_err(|e| io_error(&format!("read `{}`", path.display()), e))
+}
+
+/// Reads a sysfs attribute as a number.
+fn parse_attribute(dir: &Path, attribute: &str) -> Result<f64, Error> {
+    let value = read_attribute(dir, attribute)?;
+    value.parse().map_err(|_| Error::SPI {
+        kind: ErrorKind::Other,
+        message: format!("`{attribute}` should be a number, but it's `{value}`."),
+    })
+}
+
+/// Writes a sysfs attribute.
+fn write_attribute(dir: &Path, attribute: &str, value: &str) -> Result<(), Error> {
+    let path = dir.join(attribute);
+    fs::write(&path, value).map_err(|e| io_error(&format!("write `{}`", path.display()), e))
+}
+
+impl ErrorType for Iio {
+    type Error = Error;
+}
+
+impl SpiDevice for Iio {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        for op in operations {
+            let buf = match op {
+                Operation::Read(buf) | Operation::TransferInPlace(buf) => buf,
+                Operation::Transfer(read, _) => read,
+                Operation::Write(_) | Operation::DelayNs(_) => continue,
+            };
+
+            let bytes = self.frame()?.to_be_bytes();
+            buf.fill(0);
+            let len = buf.len().min(bytes.len());
+            buf[..len].copy_from_slice(&bytes[..len]);
+        }
+
+        Ok(())
+    }
+}
+
+impl Sensor for Iio {
+    fn read_celsius(&mut self) -> Result<f64, Error> {
+        Iio::read_celsius(self)
+    }
+}
+
+impl ThermocoupleConverter for Iio {
+    fn part(&self) -> &'static str {
+        self.part
+    }
+
+    fn range(&self) -> std::ops::RangeInclusive<f64> {
+        match self.part {
+            "MAX6675" => 0.0..=1023.75,
+            "MAX31855" => -270.0..=1800.0,
+            _ => -210.0..=1800.0,
+        }
+    }
+
+    fn resolution(&self) -> f64 {
+        self.scale / 1000.0
+    }
+
+    fn read_conversion(&mut self) -> Result<Conversion, Error> {
+        Iio::read_conversion(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::Max6675;
+
+    /// Makes a fake sysfs device with the given attributes.
+    fn fake_device(root: &Path, n: usize, attributes: &[(&str, &str)]) -> PathBuf {
+        let dir = root.join(format!("bus/iio/devices/iio:device{n}"));
+        fs::create_dir_all(&dir).unwrap();
+        for (attribute, value) in attributes {
+            let path = dir.join(attribute);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, format!("{value}\n")).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn parses_scan_types() {
+        assert_eq!(
+            ScanType::parse("be:s13/16>>3\n"),
+            Some(ScanType {
+                big_endian: true,
+                signed: true,
+                real_bits: 13,
+                storage_bits: 16,
+                shift: 3,
+            })
+        );
+        assert_eq!(ScanType::parse("le:u12/32X2>>4").unwrap().storage_bits, 32);
+        assert_eq!(ScanType::parse("be:s13/12>>3"), None);
+        assert_eq!(ScanType::parse("nonsense"), None);
+
+        let max31855 = ScanType::parse("be:s14/32>>18").unwrap();
+        assert_eq!(max31855.decode(0xF060_0000), -1000);
+    }
+
+    #[test]
+    fn finds_maxim_devices() {
+        let root = tempfile::tempdir().unwrap();
+        fake_device(
+            root.path(),
+            1,
+            &[("name", "max31855k"), ("in_temp_scale", "250")],
+        );
+        fake_device(
+            root.path(),
+            0,
+            &[("name", "max6675"), ("in_temp_scale", "250")],
+        );
+        fake_device(root.path(), 2, &[("name", "bme280")]);
+
+        let devices = devices_in(root.path()).unwrap();
+        let names: Vec<_> = devices.iter().map(|d| d.name()).collect();
+        assert_eq!(names, ["max6675", "max31855k"]);
+        assert_eq!(devices[1].part(), "MAX31855");
+
+        // no IIO at all is fine too
+        assert!(devices_in(root.path().join("nowhere")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reads_sysfs() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = fake_device(
+            root.path(),
+            0,
+            &[
+                ("name", "max31855"),
+                ("in_temp_raw", "-1000"),
+                ("in_temp_scale", "250"),
+                ("in_temp_ambient_raw", "401"),
+                ("in_temp_ambient_scale", "62.500000"),
+            ],
+        );
+
+        let mut iio = Iio::open(&dir).unwrap();
+        assert_eq!(iio.resolution(), 0.25);
+        assert_eq!(
+            iio.read_conversion(),
+            Ok(Conversion {
+                thermocouple: -250.0,
+                cold_junction: Some(25.0625),
+            })
+        );
+    }
+
+    #[test]
+    fn works_behind_the_driver() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = fake_device(
+            root.path(),
+            0,
+            &[
+                ("name", "max6675"),
+                ("in_temp_raw", "100"),
+                ("in_temp_scale", "250"),
+            ],
+        );
+
+        let mut max = Max6675::from_spi(Iio::open(&dir).unwrap());
+        max.set_conversion_time(Duration::ZERO);
+        assert_eq!(max.read_celsius(), Ok(25.0));
+
+        // something that isn't a number is an error, not a temperature
+        fs::write(dir.join("in_temp_raw"), "oops\n").unwrap();
+        assert!(matches!(max.read_celsius(), Err(Error::SPI { .. })));
+    }
+
+    #[test]
+    fn reads_the_buffer() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = fake_device(
+            root.path(),
+            0,
+            &[
+                ("name", "max6675"),
+                ("in_temp_scale", "250"),
+                ("scan_elements/in_temp_en", "0"),
+                ("scan_elements/in_temp_type", "be:s13/16>>3"),
+                ("scan_elements/in_timestamp_en", "1"),
+                ("buffer/enable", "0"),
+            ],
+        );
+
+        // two MAX6675 frames: 25° C, then an open thermocouple
+        let dev = root.path().join("iio:device0");
+        fs::write(&dev, [0x03, 0x20, 0x00, 0x04]).unwrap();
+
+        let mut iio = Iio::open(&dir).unwrap();
+        iio.enable_buffer(&dev).unwrap();
+
+        let attribute = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(attribute("scan_elements/in_temp_en"), "1");
+        assert_eq!(attribute("scan_elements/in_timestamp_en"), "0");
+        assert_eq!(attribute("buffer/enable"), "1");
+
+        assert_eq!(iio.read_celsius(), Ok(25.0));
+        assert_eq!(iio.read_celsius(), Err(Error::OpenCircuit));
+
+        drop(iio);
+        assert_eq!(attribute("buffer/enable"), "0");
+    }
+
+    #[test]
+    fn only_opens_maxim_chips() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = fake_device(root.path(), 0, &[("name", "bme280")]);
+
+        assert!(matches!(Iio::open(dir), Err(Error::SPI { .. })));
+    }
+}
@@ -0,0 +1,181 @@
+//! # mock
+//!
+//! A pretend MAX6675 for testing without any hardware.
+//!
+//! You script what the "chip" should say ahead of time, then hand it to a
+//! [`Max6675`](crate::Max6675) like any other backend.
+
+use std::collections::VecDeque;
+
+use embedded_hal::spi::{ErrorKind, ErrorType, Operation, SpiDevice};
+
+use crate::Error;
+
+/// Something the mock MAX6675 will do when it's read from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MockEvent {
+    /// Sends back this raw 16-bit word.
+    Frame(u16),
+    /// Sends back these bytes, for chips with longer frames or registers.
+    Bytes(Vec<u8>),
+    /// Sends back fewer bytes than were asked for.
+    ShortRead,
+    /// Fails with an SPI error of this kind.
+    SpiError(ErrorKind),
+}
+
+/// A scripted, simulated MAX6675.
+///
+/// Each read consumes one [`MockEvent`] from the script. Once the script runs
+/// out, the mock acts like nothing's connected and returns
+/// [`Error::ReceivedNothing`].
+///
+/// ## Example
+///
+/// ```
+///
+/// use linux_max6675::{backend::mock::MockMax6675, Error, Max6675};
+///
+/// let mut mock = MockMax6675::new();
+/// mock.push_celsius(21.5).push_open_circuit();
+///
+/// let mut max = Max6675::from_spi(mock);
+///
+/// assert_eq!(max.read_celsius(), Ok(21.5));
+/// assert_eq!(max.read_celsius(), Err(Error::OpenCircuit));
+/// assert_eq!(max.read_celsius(), Err(Error::ReceivedNothing));
+///
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MockMax6675 {
+    script: VecDeque<MockEvent>,
+    reads: usize,
+    written: Vec<Vec<u8>>,
+}
+
+impl MockMax6675 {
+    /// Creates a mock with an empty script.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encodes a temperature into the raw word a real MAX6675 would send.
+    ///
+    /// The temperature is rounded to the nearest quarter degree and clamped to
+    /// the chip's range of 0 to 1023.75° C.
+    pub fn encode_celsius(celsius: f64) -> u16 {
+        let quarters = (celsius * 4.0).round().clamp(0.0, 4095.0) as u16;
+        quarters << 3
+    }
+
+    /// Adds an event to the end of the script.
+    pub fn push(&mut self, event: MockEvent) -> &mut Self {
+        self.script.push_back(event);
+        self
+    }
+
+    /// Adds a raw 16-bit word to the script.
+    pub fn push_frame(&mut self, raw: u16) -> &mut Self {
+        self.push(MockEvent::Frame(raw))
+    }
+
+    /// Adds raw bytes to the script.
+    pub fn push_bytes(&mut self, bytes: impl Into<Vec<u8>>) -> &mut Self {
+        self.push(MockEvent::Bytes(bytes.into()))
+    }
+
+    /// Adds a reading of the given temperature to the script.
+    pub fn push_celsius(&mut self, celsius: f64) -> &mut Self {
+        self.push_frame(Self::encode_celsius(celsius))
+    }
+
+    /// Adds a whole temperature profile to the script, one reading each.
+    pub fn push_profile(&mut self, profile: impl IntoIterator<Item = f64>) -> &mut Self {
+        for celsius in profile {
+            self.push_celsius(celsius);
+        }
+        self
+    }
+
+    /// Adds a reading with the open thermocouple bit (D2) set.
+    pub fn push_open_circuit(&mut self) -> &mut Self {
+        self.push_frame(0x04)
+    }
+
+    /// Adds a read that comes back short.
+    pub fn push_short_read(&mut self) -> &mut Self {
+        self.push(MockEvent::ShortRead)
+    }
+
+    /// Adds a read that fails with an SPI error.
+    pub fn push_spi_error(&mut self, kind: ErrorKind) -> &mut Self {
+        self.push(MockEvent::SpiError(kind))
+    }
+
+    /// How many reads the mock has seen so far.
+    pub fn reads(&self) -> usize {
+        self.reads
+    }
+
+    /// Everything that's been written to the mock, one entry per write.
+    pub fn written(&self) -> &[Vec<u8>] {
+        &self.written
+    }
+
+    /// How many events are left in the script.
+    pub fn remaining(&self) -> usize {
+        self.script.len()
+    }
+}
+
+impl ErrorType for MockMax6675 {
+    type Error = Error;
+}
+
+impl SpiDevice for MockMax6675 {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        for op in operations {
+            let buf = match op {
+                Operation::Read(buf) | Operation::TransferInPlace(buf) => buf,
+                Operation::Transfer(read, _) => read,
+                Operation::Write(buf) => {
+                    self.written.push(buf.to_vec());
+                    continue;
+                }
+                Operation::DelayNs(_) => continue,
+            };
+
+            self.reads += 1;
+
+            match self.script.pop_front() {
+                Some(MockEvent::Frame(raw)) => {
+                    buf.fill(0);
+                    let bytes = raw.to_be_bytes();
+                    let len = buf.len().min(bytes.len());
+                    buf[..len].copy_from_slice(&bytes[..len]);
+                }
+                Some(MockEvent::Bytes(bytes)) => {
+                    buf.fill(0);
+                    let len = buf.len().min(bytes.len());
+                    buf[..len].copy_from_slice(&bytes[..len]);
+                }
+                Some(MockEvent::SpiError(kind)) => {
+                    return Err(Error::SPI {
+                        kind,
+                        message: "Simulated SPI error".into(),
+                    })
+                }
+                Some(MockEvent::ShortRead) | None => return Err(Error::ReceivedNothing),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::spi::SpiDevice for MockMax6675 {
+    async fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        SpiDevice::transaction(self, operations)
+    }
+}
@@ -0,0 +1,41 @@
+//! # backend
+//!
+//! SPI devices that the [`Max6675`](crate::Max6675) can talk through.
+//!
+//! Each one implements [`embedded_hal::spi::SpiDevice`] with our own
+//! [`Error`](crate::Error) type, so you get the same errors no matter which
+//! backend you pick.
+
+pub mod bitbang;
+#[cfg(feature = "iio")]
+pub mod iio;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+#[cfg(feature = "rppal")]
+pub mod rppal;
+#[cfg(feature = "spidev")]
+pub mod spidev;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+/// Turns an I/O error into an SPI [`Error`](crate::Error), noting what we
+/// were doing.
+#[cfg(any(feature = "spidev", feature = "iio"))]
+pub(crate) fn io_error(doing: &str, e: std::io::Error) -> crate::Error {
+    crate::Error::SPI {
+        kind: embedded_hal::spi::ErrorKind::Other,
+        message: format!("Failed to {doing}: {e}"),
+    }
+}
+
+/// Splits a delay into chunks of whole microseconds that each fit in a
+/// spidev transfer's `delay_usecs`, rounding up.
+#[cfg(any(feature = "spidev", feature = "rppal"))]
+pub(crate) fn delay_chunks(ns: u32) -> impl Iterator<Item = u16> {
+    let mut us = ns.div_ceil(1000);
+    std::iter::from_fn(move || {
+        let chunk = us.min(u32::from(u16::MAX));
+        us -= chunk;
+        (chunk > 0).then_some(chunk as u16)
+    })
+}
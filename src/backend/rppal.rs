@@ -0,0 +1,225 @@
+//! # rppal
+//!
+//! An SPI backend built on the [`rppal`](https://docs.rs/rppal) crate.
+
+use std::path::Path;
+
+use ::rppal::spi::{Bus, Mode, Segment, SlaveSelect, Spi};
+use embedded_hal::spi::{ErrorKind, ErrorType, Operation, SpiDevice};
+
+use super::delay_chunks;
+use crate::{Error, CLOCK_SPEED};
+
+impl From<::rppal::spi::Error> for Error {
+    fn from(e: ::rppal::spi::Error) -> Self {
+        Error::SPI {
+            kind: ErrorKind::Other,
+            message: e.to_string(),
+        }
+    }
+}
+
+/// An [`rppal`](https://docs.rs/rppal) SPI connection, usable as an
+/// [`SpiDevice`].
+///
+/// ## Example
+///
+/// ```no_run
+///
+/// use linux_max6675::{Max6675, RppalSpi};
+/// use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
+///
+/// let spi = Spi::new(Bus::Spi0, SlaveSelect::Ss0, 1_000_000, Mode::Mode1).unwrap();
+/// let mut max = Max6675::from_spi(RppalSpi::from(spi));
+///
+/// println!("it's {}° celsius in here!", max.read_celsius().unwrap());
+///
+/// ```
+#[derive(Debug)]
+pub struct RppalSpi {
+    spi: Spi,
+}
+
+impl RppalSpi {
+    /// Opens the SPI at the given spidev path, like `/dev/spidev0.0`.
+    ///
+    /// The SPI is configured for the MAX6675: mode 1 (CPOL = 0, CPHA = 1) at
+    /// [`CLOCK_SPEED`].
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let (bus, slave_select) = parse_spidev_path(path.as_ref())?;
+        let spi = Spi::new(bus, slave_select, CLOCK_SPEED, Mode::Mode1)?;
+        Ok(Self { spi })
+    }
+
+    /// Gives back the underlying [`Spi`].
+    pub fn into_inner(self) -> Spi {
+        self.spi
+    }
+}
+
+impl From<Spi> for RppalSpi {
+    fn from(spi: Spi) -> Self {
+        Self { spi }
+    }
+}
+
+impl ErrorType for RppalSpi {
+    type Error = Error;
+}
+
+impl SpiDevice for RppalSpi {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        // `transfer_segments` doesn't say how much it got, but a plain read
+        // does, which covers the MAX6675's one and only kind of transaction
+        if let [Operation::Read(buf)] = operations {
+            if self.spi.read(buf)? < buf.len() {
+                return Err(Error::ReceivedNothing);
+            }
+            return Ok(());
+        }
+
+        // rppal only transfers as much as the shorter buffer holds, and can't
+        // read into the buffer it's writing from, so transfers go through
+        // some scratch space
+        let mut scratch: Vec<(Vec<u8>, Vec<u8>)> = operations
+            .iter()
+            .filter_map(|op| match op {
+                Operation::Transfer(read, write) => {
+                    let len = read.len().max(write.len());
+                    let mut tx = write.to_vec();
+                    tx.resize(len, 0);
+                    Some((vec![0; len], tx))
+                }
+                Operation::TransferInPlace(buf) => Some((vec![0; buf.len()], buf.to_vec())),
+                _ => None,
+            })
+            .collect();
+
+        {
+            // one call, so CS stays asserted for the whole transaction
+            let segments = segments(operations, &mut scratch);
+            if !segments.is_empty() {
+                self.spi.transfer_segments(&segments)?;
+            }
+        }
+
+        // copy the transfers back into the caller's buffers
+        let mut scratch_iter = scratch.iter();
+        for op in operations.iter_mut() {
+            match op {
+                Operation::Transfer(read, _) => {
+                    let (rx, _) = scratch_iter.next().expect("scratch for each transfer");
+                    read.copy_from_slice(&rx[..read.len()]);
+                }
+                Operation::TransferInPlace(buf) => {
+                    let (rx, _) = scratch_iter.next().expect("scratch for each transfer");
+                    buf.copy_from_slice(rx);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Lays out a transaction as segments, with transfers going through
+/// `scratch`.
+///
+/// Delays become empty segments that just wait, so they happen right where
+/// they are in the transaction, with CS still asserted.
+fn segments<'a>(
+    operations: &'a mut [Operation<'_, u8>],
+    scratch: &'a mut [(Vec<u8>, Vec<u8>)],
+) -> Vec<Segment<'a, 'a>> {
+    let mut segments = Vec::with_capacity(operations.len());
+    let mut scratch_iter = scratch.iter_mut();
+
+    for op in operations.iter_mut() {
+        match op {
+            Operation::Read(buf) => segments.push(Segment::with_read(buf)),
+            Operation::Write(buf) => segments.push(Segment::with_write(buf)),
+            Operation::Transfer(..) | Operation::TransferInPlace(_) => {
+                let (rx, tx) = scratch_iter.next().expect("scratch for each transfer");
+                segments.push(Segment::new(rx, tx));
+            }
+            Operation::DelayNs(ns) => {
+                for us in delay_chunks(*ns) {
+                    let mut wait = Segment::with_write(&[]);
+                    wait.set_delay(us);
+                    segments.push(wait);
+                }
+            }
+        }
+    }
+
+    segments
+}
+
+/// Splits a path like `/dev/spidev0.1` into its bus and slave select.
+fn parse_spidev_path(path: &Path) -> Result<(Bus, SlaveSelect), Error> {
+    let invalid = || Error::InvalidPath {
+        path: path.display().to_string(),
+    };
+
+    let (bus, slave_select) = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix("spidev"))
+        .and_then(|numbers| numbers.split_once('.'))
+        .ok_or_else(invalid)?;
+
+    let bus = match bus.parse::<u8>().map_err(|_| invalid())? {
+        0 => Bus::Spi0,
+        1 => Bus::Spi1,
+        2 => Bus::Spi2,
+        3 => Bus::Spi3,
+        4 => Bus::Spi4,
+        5 => Bus::Spi5,
+        6 => Bus::Spi6,
+        _ => return Err(invalid()),
+    };
+
+    let slave_select = match slave_select.parse::<u8>().map_err(|_| invalid())? {
+        0 => SlaveSelect::Ss0,
+        1 => SlaveSelect::Ss1,
+        2 => SlaveSelect::Ss2,
+        3 => SlaveSelect::Ss3,
+        4 => SlaveSelect::Ss4,
+        5 => SlaveSelect::Ss5,
+        6 => SlaveSelect::Ss6,
+        7 => SlaveSelect::Ss7,
+        8 => SlaveSelect::Ss8,
+        9 => SlaveSelect::Ss9,
+        10 => SlaveSelect::Ss10,
+        11 => SlaveSelect::Ss11,
+        12 => SlaveSelect::Ss12,
+        13 => SlaveSelect::Ss13,
+        14 => SlaveSelect::Ss14,
+        15 => SlaveSelect::Ss15,
+        _ => return Err(invalid()),
+    };
+
+    Ok((bus, slave_select))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_happen_where_they_are() {
+        let mut buf = [0; 2];
+        let mut operations = [
+            Operation::DelayNs(1_500),
+            Operation::Read(&mut buf),
+            // too long for one segment's delay
+            Operation::DelayNs(70_000_000),
+            Operation::Write(&[1]),
+        ];
+
+        let segments = segments(&mut operations, &mut []);
+        let layout: Vec<_> = segments.iter().map(|s| (s.len(), s.delay())).collect();
+        assert_eq!(layout, [(0, 2), (2, 0), (0, 65_535), (0, 4_465), (1, 0)]);
+    }
+}
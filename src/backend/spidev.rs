@@ -0,0 +1,411 @@
+//! # spidev
+//!
+//! An SPI backend that talks straight to the Linux kernel's spidev interface
+//! through `ioctl`s. No Raspberry Pi required!
+//!
+//! See the kernel's [spidev documentation](https://docs.kernel.org/spi/spidev.html)
+//! for details on the interface.
+
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::fd::AsRawFd,
+    path::Path,
+    ptr,
+};
+
+use embedded_hal::spi::{ErrorType, Mode, Operation, Phase, Polarity, SpiDevice};
+
+use super::{delay_chunks, io_error};
+use crate::{Error, CLOCK_SPEED};
+
+/// SPI mode 1: CPOL = 0, CPHA = 1. This is what the MAX6675 speaks.
+const SPI_MODE_1: u8 = 0x01;
+
+/// The kernel's `struct spi_ioc_transfer`, from `linux/spi/spidev.h`.
+#[repr(C)]
+#[derive(Debug, Default)]
+struct SpiIocTransfer {
+    tx_buf: u64,
+    rx_buf: u64,
+    len: u32,
+    speed_hz: u32,
+    delay_usecs: u16,
+    bits_per_word: u8,
+    cs_change: u8,
+    tx_nbits: u8,
+    rx_nbits: u8,
+    word_delay_usecs: u8,
+    pad: u8,
+}
+
+/// The magic number for all spidev `ioctl`s.
+const SPI_IOC_MAGIC: u32 = b'k' as u32;
+
+// a few architectures lay out their ioctl numbers a little differently
+#[cfg(any(
+    target_arch = "mips",
+    target_arch = "mips64",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "sparc64"
+))]
+const IOC_WRITE: (u32, u32) = (4, 13);
+#[cfg(not(any(
+    target_arch = "mips",
+    target_arch = "mips64",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "sparc64"
+)))]
+const IOC_WRITE: (u32, u32) = (1, 14);
+
+/// `_IOC_READ`, which is the same everywhere.
+const IOC_READ: u32 = 2;
+
+/// Builds an `_IOW(SPI_IOC_MAGIC, nr, size)` request number.
+const fn iow(nr: u32, size: usize) -> u32 {
+    let (dir, size_bits) = IOC_WRITE;
+    (dir << (16 + size_bits)) | ((size as u32) << 16) | (SPI_IOC_MAGIC << 8) | nr
+}
+
+/// Builds an `_IOR(SPI_IOC_MAGIC, nr, size)` request number.
+const fn ior(nr: u32, size: usize) -> u32 {
+    let (_, size_bits) = IOC_WRITE;
+    (IOC_READ << (16 + size_bits)) | ((size as u32) << 16) | (SPI_IOC_MAGIC << 8) | nr
+}
+
+const SPI_IOC_RD_MODE: u32 = ior(1, size_of::<u8>());
+const SPI_IOC_RD_BITS_PER_WORD: u32 = ior(3, size_of::<u8>());
+const SPI_IOC_RD_MAX_SPEED_HZ: u32 = ior(4, size_of::<u32>());
+
+const SPI_IOC_WR_MODE: u32 = iow(1, size_of::<u8>());
+const SPI_IOC_WR_BITS_PER_WORD: u32 = iow(3, size_of::<u8>());
+const SPI_IOC_WR_MAX_SPEED_HZ: u32 = iow(4, size_of::<u32>());
+
+/// `SPI_IOC_MESSAGE(n)`: performs `n` transfers without releasing CS.
+const fn spi_ioc_message(n: usize) -> u32 {
+    iow(0, n * size_of::<SpiIocTransfer>())
+}
+
+/// A Linux spidev device, like `/dev/spidev0.0`, usable as an [`SpiDevice`].
+///
+/// ## Example
+///
+/// ```no_run
+///
+/// use linux_max6675::{Max6675, Spidev};
+///
+/// let spi = Spidev::open("/dev/spidev0.0").unwrap();
+/// let mut max = Max6675::from_spi(spi);
+///
+/// println!("it's {}° celsius in here!", max.read_celsius().unwrap());
+///
+/// ```
+#[derive(Debug)]
+pub struct Spidev {
+    file: File,
+    speed_hz: u32,
+    /// How the device was set up before we got to it, if we're supposed to
+    /// put it back that way when we're done.
+    restore: Option<Settings>,
+}
+
+/// The spidev settings we change when opening a device.
+#[derive(Clone, Copy, Debug)]
+struct Settings {
+    mode: u8,
+    bits_per_word: u8,
+    speed_hz: u32,
+}
+
+impl Spidev {
+    /// Opens the spidev at the given path, like `/dev/spidev0.0`.
+    ///
+    /// The SPI is configured for the MAX6675: mode 1 (CPOL = 0, CPHA = 1),
+    /// 8 bits per word, and [`CLOCK_SPEED`].
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::open_with(path.as_ref(), false)
+    }
+
+    /// Like [`Spidev::open`], but puts the device's mode, bits per word, and
+    /// clock speed back the way they were when it's dropped.
+    ///
+    /// This is for poking at devices that might belong to something else,
+    /// like [`Discovery`](crate::discover::Discovery) does.
+    pub(crate) fn open_and_restore(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::open_with(path.as_ref(), true)
+    }
+
+    fn open_with(path: &Path, restore: bool) -> Result<Self, Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|e| io_error(&format!("open `{}`", path.display()), e))?;
+
+        let mut spidev = Self {
+            file,
+            speed_hz: CLOCK_SPEED,
+            restore: None,
+        };
+
+        if restore {
+            // if configuring fails halfway, dropping `spidev` puts it back
+            spidev.restore = Some(Settings {
+                mode: spidev.read_setting(SPI_IOC_RD_MODE, "read the SPI mode")?,
+                bits_per_word: spidev
+                    .read_setting(SPI_IOC_RD_BITS_PER_WORD, "read bits per word")?,
+                speed_hz: spidev.read_setting(SPI_IOC_RD_MAX_SPEED_HZ, "read the clock speed")?,
+            });
+        }
+
+        spidev.write_setting(SPI_IOC_WR_MODE, &SPI_MODE_1, "set the SPI mode")?;
+        spidev.write_setting(SPI_IOC_WR_BITS_PER_WORD, &8_u8, "set bits per word")?;
+        spidev.set_speed(CLOCK_SPEED)?;
+
+        Ok(spidev)
+    }
+
+    /// Changes the maximum clock speed, in Hz.
+    ///
+    /// The MAX6675 can't go above 4.3 MHz, so please don't!
+    pub fn set_speed(&mut self, speed_hz: u32) -> Result<(), Error> {
+        self.write_setting(SPI_IOC_WR_MAX_SPEED_HZ, &speed_hz, "set the clock speed")?;
+        self.speed_hz = speed_hz;
+        Ok(())
+    }
+
+    /// Changes the SPI mode.
+    ///
+    /// The MAX6675 wants mode 1, which is what [`Spidev::open`] sets up, but
+    /// some of its siblings (like the MAX31855) want something else.
+    pub fn set_mode(&mut self, mode: Mode) -> Result<(), Error> {
+        let mut bits = 0_u8;
+        if mode.phase == Phase::CaptureOnSecondTransition {
+            bits |= 0x01;
+        }
+        if mode.polarity == Polarity::IdleHigh {
+            bits |= 0x02;
+        }
+        self.write_setting(SPI_IOC_WR_MODE, &bits, "set the SPI mode")
+    }
+
+    /// Writes one of the spidev settings with an `ioctl`.
+    fn write_setting<T>(&mut self, request: u32, value: &T, doing: &str) -> Result<(), Error> {
+        // SAFETY: `value` is a valid pointer to the type the request expects
+        let ret = unsafe {
+            libc::ioctl(
+                self.file.as_raw_fd(),
+                request as libc::Ioctl,
+                value as *const T,
+            )
+        };
+
+        if ret < 0 {
+            return Err(io_error(doing, io::Error::last_os_error()));
+        }
+
+        Ok(())
+    }
+
+    /// Reads one of the spidev settings with an `ioctl`.
+    fn read_setting<T: Default>(&self, request: u32, doing: &str) -> Result<T, Error> {
+        let mut value = T::default();
+        // SAFETY: `value` is a valid pointer to the type the request expects
+        let ret = unsafe {
+            libc::ioctl(
+                self.file.as_raw_fd(),
+                request as libc::Ioctl,
+                &mut value as *mut T,
+            )
+        };
+
+        if ret < 0 {
+            return Err(io_error(doing, io::Error::last_os_error()));
+        }
+
+        Ok(value)
+    }
+
+    /// Describes a single transfer for the kernel.
+    ///
+    /// Either buffer can be null, which makes it a half-duplex transfer.
+    fn describe(&self, rx: *mut u8, tx: *const u8, len: usize) -> SpiIocTransfer {
+        SpiIocTransfer {
+            tx_buf: tx as u64,
+            rx_buf: rx as u64,
+            len: len as u32,
+            speed_hz: self.speed_hz,
+            ..Default::default()
+        }
+    }
+}
+
+impl Drop for Spidev {
+    fn drop(&mut self) {
+        if let Some(settings) = self.restore.take() {
+            // nowhere to report these, and we tried our best
+            let _ = self.write_setting(SPI_IOC_WR_MODE, &settings.mode, "restore the SPI mode");
+            let _ = self.write_setting(
+                SPI_IOC_WR_BITS_PER_WORD,
+                &settings.bits_per_word,
+                "restore bits per word",
+            );
+            let _ = self.write_setting(
+                SPI_IOC_WR_MAX_SPEED_HZ,
+                &settings.speed_hz,
+                "restore the clock speed",
+            );
+        }
+    }
+}
+
+impl Spidev {
+    /// Describes a transaction for one `SPI_IOC_MESSAGE`, with mismatched
+    /// transfers going through `scratch`.
+    ///
+    /// Delays become empty transfers that just wait, so the kernel does them
+    /// right where they are in the transaction, with CS still asserted.
+    fn transfers(
+        &self,
+        operations: &mut [Operation<'_, u8>],
+        scratch: &mut [(Vec<u8>, Vec<u8>)],
+    ) -> Vec<SpiIocTransfer> {
+        let mut transfers = Vec::with_capacity(operations.len());
+        let mut scratch_iter = scratch.iter_mut();
+
+        for op in operations.iter_mut() {
+            let transfer = match op {
+                Operation::Read(buf) => self.describe(buf.as_mut_ptr(), ptr::null(), buf.len()),
+                Operation::Write(buf) => self.describe(ptr::null_mut(), buf.as_ptr(), buf.len()),
+                Operation::Transfer(read, write) if read.len() == write.len() => {
+                    self.describe(read.as_mut_ptr(), write.as_ptr(), read.len())
+                }
+                Operation::Transfer(..) => {
+                    let (rx, tx) = scratch_iter.next().expect("scratch for each transfer");
+                    self.describe(rx.as_mut_ptr(), tx.as_ptr(), rx.len())
+                }
+                Operation::TransferInPlace(buf) => {
+                    self.describe(buf.as_mut_ptr(), buf.as_ptr(), buf.len())
+                }
+                Operation::DelayNs(ns) => {
+                    transfers.extend(delay_chunks(*ns).map(|us| SpiIocTransfer {
+                        delay_usecs: us,
+                        ..self.describe(ptr::null_mut(), ptr::null(), 0)
+                    }));
+                    continue;
+                }
+            };
+
+            transfers.push(transfer);
+        }
+
+        transfers
+    }
+}
+
+impl ErrorType for Spidev {
+    type Error = Error;
+}
+
+impl SpiDevice for Spidev {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        // `Transfer`s with mismatched lengths need some scratch space, since
+        // the kernel wants both buffers to be the same size
+        let mut scratch: Vec<(Vec<u8>, Vec<u8>)> = operations
+            .iter()
+            .filter_map(|op| match op {
+                Operation::Transfer(read, write) if read.len() != write.len() => {
+                    let len = read.len().max(write.len());
+                    let mut tx = write.to_vec();
+                    tx.resize(len, 0);
+                    Some((vec![0; len], tx))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let transfers = self.transfers(operations, &mut scratch);
+        if transfers.is_empty() {
+            return Ok(());
+        }
+
+        // SAFETY: every transfer points into a buffer that outlives this call
+        let ret = unsafe {
+            libc::ioctl(
+                self.file.as_raw_fd(),
+                spi_ioc_message(transfers.len()) as libc::Ioctl,
+                transfers.as_ptr(),
+            )
+        };
+
+        if ret < 0 {
+            return Err(io_error("transfer", io::Error::last_os_error()));
+        }
+
+        let expected: usize = transfers.iter().map(|t| t.len as usize).sum();
+        if (ret as usize) < expected {
+            return Err(Error::ReceivedNothing);
+        }
+
+        // copy any mismatched transfers back into the caller's buffers
+        let mut scratch_iter = scratch.iter();
+        for op in operations.iter_mut() {
+            if let Operation::Transfer(read, write) = op {
+                if read.len() != write.len() {
+                    let (rx, _) = scratch_iter.next().expect("scratch for each transfer");
+                    read.copy_from_slice(&rx[..read.len()]);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(any(
+    target_arch = "mips",
+    target_arch = "mips64",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "sparc64"
+)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ioctl_numbers_match_the_kernel() {
+        assert_eq!(size_of::<SpiIocTransfer>(), 32);
+        assert_eq!(SPI_IOC_WR_MODE, 0x4001_6b01);
+        assert_eq!(SPI_IOC_WR_BITS_PER_WORD, 0x4001_6b03);
+        assert_eq!(SPI_IOC_WR_MAX_SPEED_HZ, 0x4004_6b04);
+        assert_eq!(SPI_IOC_RD_MODE, 0x8001_6b01);
+        assert_eq!(SPI_IOC_RD_BITS_PER_WORD, 0x8001_6b03);
+        assert_eq!(SPI_IOC_RD_MAX_SPEED_HZ, 0x8004_6b04);
+        assert_eq!(spi_ioc_message(1), 0x4020_6b00);
+    }
+
+    #[test]
+    fn delays_happen_where_they_are() {
+        let spidev = Spidev {
+            file: File::open("/dev/null").unwrap(),
+            speed_hz: CLOCK_SPEED,
+            restore: None,
+        };
+
+        let mut buf = [0; 2];
+        let mut operations = [
+            Operation::DelayNs(1_500),
+            Operation::Read(&mut buf),
+            // too long for one transfer's delay
+            Operation::DelayNs(70_000_000),
+            Operation::Write(&[1]),
+        ];
+
+        let transfers = spidev.transfers(&mut operations, &mut []);
+        let layout: Vec<_> = transfers.iter().map(|t| (t.len, t.delay_usecs)).collect();
+        assert_eq!(layout, [(0, 2), (2, 0), (0, 65_535), (0, 4_465), (1, 0)]);
+    }
+}
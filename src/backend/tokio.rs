@@ -0,0 +1,121 @@
+//! # tokio
+//!
+//! Async pieces for running the [`asynch::Max6675`](crate::asynch::Max6675)
+//! on tokio: a [`TokioSpidev`] that moves blocking spidev transfers off the
+//! runtime, and a [`TokioDelay`] that waits with tokio's timer.
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use embedded_hal::spi::{ErrorType, Operation};
+
+use crate::{backend::spidev::Spidev, Error};
+
+/// A [`Spidev`] usable as an async [`SpiDevice`](embedded_hal_async::spi::SpiDevice).
+///
+/// Transfers run on tokio's blocking thread pool, so they never stall the
+/// runtime.
+#[derive(Clone, Debug)]
+pub struct TokioSpidev {
+    spidev: Arc<Mutex<Spidev>>,
+}
+
+impl TokioSpidev {
+    /// Opens the spidev at the given path, like `/dev/spidev0.0`.
+    ///
+    /// See [`Spidev::open`] for how it's configured.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(Self::from(Spidev::open(path)?))
+    }
+}
+
+impl From<Spidev> for TokioSpidev {
+    fn from(spidev: Spidev) -> Self {
+        Self {
+            spidev: Arc::new(Mutex::new(spidev)),
+        }
+    }
+}
+
+/// An [`Operation`] that owns its buffers, so it can cross over to another thread.
+enum OwnedOperation {
+    Read(Vec<u8>),
+    Write(Vec<u8>),
+    Transfer(Vec<u8>, Vec<u8>),
+    TransferInPlace(Vec<u8>),
+    DelayNs(u32),
+}
+
+impl ErrorType for TokioSpidev {
+    type Error = Error;
+}
+
+impl embedded_hal_async::spi::SpiDevice for TokioSpidev {
+    async fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        let mut owned: Vec<OwnedOperation> = operations
+            .iter()
+            .map(|op| match op {
+                Operation::Read(buf) => OwnedOperation::Read(vec![0; buf.len()]),
+                Operation::Write(buf) => OwnedOperation::Write(buf.to_vec()),
+                Operation::Transfer(read, write) => {
+                    OwnedOperation::Transfer(vec![0; read.len()], write.to_vec())
+                }
+                Operation::TransferInPlace(buf) => OwnedOperation::TransferInPlace(buf.to_vec()),
+                Operation::DelayNs(ns) => OwnedOperation::DelayNs(*ns),
+            })
+            .collect();
+
+        let spidev = Arc::clone(&self.spidev);
+        let owned = ::tokio::task::spawn_blocking(move || {
+            let mut ops: Vec<Operation<'_, u8>> = owned
+                .iter_mut()
+                .map(|op| match op {
+                    OwnedOperation::Read(buf) => Operation::Read(buf),
+                    OwnedOperation::Write(buf) => Operation::Write(buf),
+                    OwnedOperation::Transfer(read, write) => Operation::Transfer(read, write),
+                    OwnedOperation::TransferInPlace(buf) => Operation::TransferInPlace(buf),
+                    OwnedOperation::DelayNs(ns) => Operation::DelayNs(*ns),
+                })
+                .collect();
+
+            let mut spidev = spidev
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            embedded_hal::spi::SpiDevice::transaction(&mut *spidev, &mut ops)?;
+            drop(ops);
+
+            Ok::<_, Error>(owned)
+        })
+        .await
+        .map_err(|e| Error::SPI {
+            kind: embedded_hal::spi::ErrorKind::Other,
+            message: format!("The blocking SPI task failed: {e}"),
+        })??;
+
+        // copy everything we read back into the caller's buffers
+        for (op, owned) in operations.iter_mut().zip(owned) {
+            match (op, owned) {
+                (Operation::Read(buf), OwnedOperation::Read(read))
+                | (Operation::Transfer(buf, _), OwnedOperation::Transfer(read, _))
+                | (Operation::TransferInPlace(buf), OwnedOperation::TransferInPlace(read)) => {
+                    buf.copy_from_slice(&read);
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A delay that waits with tokio's timer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioDelay;
+
+impl embedded_hal_async::delay::DelayNs for TokioDelay {
+    async fn delay_ns(&mut self, ns: u32) {
+        ::tokio::time::sleep(std::time::Duration::from_nanos(u64::from(ns))).await;
+    }
+}
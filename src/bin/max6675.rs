@@ -0,0 +1,287 @@
+//! # max6675
+//!
+//! Reads a MAX6675 from the shell.
+//!
+//! ```text
+//! $ max6675 /dev/spidev0.0
+//! 23.25
+//! $ max6675 --unit fahrenheit --interval 1 --count 3
+//! 73.85
+//! 73.85
+//! 74.3
+//! ```
+//!
+//! The exit code tells you what went wrong, if anything. See `max6675 --help`.
+
+use std::{ffi::OsString, path::PathBuf, process::ExitCode, time::Duration};
+
+use clap::{Parser, ValueEnum};
+use embedded_hal::spi::SpiDevice;
+use linux_max6675::{Error, Max6675, Reading, Temperature};
+
+/// Exit codes, so scripts can tell failures apart.
+mod exit {
+    pub const OTHER: u8 = 1;
+    pub const OPEN_CIRCUIT: u8 = 2;
+    pub const RECEIVED_NOTHING: u8 = 3;
+    pub const SPI: u8 = 4;
+    pub const DEVICE_NOT_PRESENT: u8 = 5;
+}
+
+/// Reads temperatures from a MAX6675 thermocouple converter.
+#[derive(Debug, Parser)]
+#[command(
+    name = "max6675",
+    version,
+    after_help = "Exit codes:\n  \
+        0  success\n  \
+        1  any other error (bad arguments, impossible frames, ...)\n  \
+        2  the thermocouple is open\n  \
+        3  the SPI bus received nothing\n  \
+        4  an SPI error\n  \
+        5  no MAX6675 seems to be connected"
+)]
+struct Args {
+    /// The spidev to read from.
+    #[arg(default_value = "/dev/spidev0.0")]
+    device: PathBuf,
+
+    /// Use rppal on this SPI bus instead of the spidev path.
+    #[cfg(feature = "rppal")]
+    #[arg(long, requires = "slave_select")]
+    bus: Option<u8>,
+
+    /// Use rppal with this slave select instead of the spidev path.
+    #[cfg(feature = "rppal")]
+    #[arg(long, requires = "bus")]
+    slave_select: Option<u8>,
+
+    /// What to print.
+    #[arg(short, long, value_enum, default_value_t = Unit::Celsius)]
+    unit: Unit,
+
+    /// Keep reading, waiting this many seconds between reads. Errors are
+    /// printed, and reading carries on.
+    #[arg(short, long, value_parser = parse_interval)]
+    interval: Option<Duration>,
+
+    /// Stop after this many reads. Only makes sense with `--interval`. The
+    /// exit code is from the last read.
+    #[arg(short = 'n', long, requires = "interval")]
+    count: Option<u64>,
+}
+
+/// Parses a number of seconds, which has to be something we can sleep for.
+fn parse_interval(s: &str) -> Result<Duration, String> {
+    let seconds: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(format!("`{s}` isn't a number of seconds"));
+    }
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Unit {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+    /// The raw 16-bit word, in hex, even if it's open or impossible.
+    Raw,
+}
+
+impl Unit {
+    /// Formats a word from the chip.
+    ///
+    /// Raw words are printed as-is, since seeing the odd ones is the whole
+    /// point. Everything else has to be a valid frame with a thermocouple
+    /// attached.
+    fn format(self, raw: u16) -> Result<String, Error> {
+        let convert: fn(Temperature) -> f64 = match self {
+            Unit::Raw => return Ok(format!("{raw:#06x}")),
+            Unit::Celsius => Temperature::celsius,
+            Unit::Fahrenheit => Temperature::fahrenheit,
+            Unit::Kelvin => Temperature::kelvin,
+        };
+
+        let reading = Reading::from_raw(raw)?;
+        if reading.is_open() {
+            return Err(Error::OpenCircuit);
+        }
+
+        Ok(convert(reading.temperature()).to_string())
+    }
+}
+
+fn exit_code(e: &Error) -> u8 {
+    match e {
+        Error::OpenCircuit => exit::OPEN_CIRCUIT,
+        Error::ReceivedNothing => exit::RECEIVED_NOTHING,
+        Error::SPI { .. } => exit::SPI,
+        Error::DeviceNotPresent { .. } => exit::DEVICE_NOT_PRESENT,
+        _ => exit::OTHER,
+    }
+}
+
+fn run<SPI>(mut max: Max6675<SPI>, args: &Args) -> Result<(), Error>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    let Some(interval) = args.interval else {
+        // just the one, then
+        println!("{}", args.unit.format(max.read_raw()?)?);
+        return Ok(());
+    };
+
+    let mut reads = 0;
+
+    loop {
+        let line = max.read_raw().and_then(|raw| args.unit.format(raw));
+
+        reads += 1;
+        if args.count.is_some_and(|count| reads >= count) {
+            // the last read decides the exit code
+            println!("{}", line?);
+            return Ok(());
+        }
+
+        // one bad read shouldn't stop us
+        match line {
+            Ok(line) => println!("{line}"),
+            Err(e) => eprintln!("max6675: {e}"),
+        }
+
+        std::thread::sleep(interval);
+    }
+}
+
+/// Parses the arguments, or works out what to exit with if we can't (or if
+/// we were just asked for `--help`).
+fn parse_args<I, T>(args: I) -> Result<Args, u8>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    // clap exits with 2 on bad arguments, which we already use for open circuits
+    Args::try_parse_from(args).map_err(|e| {
+        let _ = e.print();
+        if e.use_stderr() {
+            exit::OTHER
+        } else {
+            0
+        }
+    })
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args_os()) {
+        Ok(args) => args,
+        Err(code) => return ExitCode::from(code),
+    };
+
+    #[cfg(feature = "rppal")]
+    let result = match (args.bus, args.slave_select) {
+        (Some(bus), Some(ss)) => linux_max6675::RppalSpi::open(format!("/dev/spidev{bus}.{ss}"))
+            .and_then(|spi| run(Max6675::from_spi(spi), &args)),
+        _ => Max6675::new(&args.device).and_then(|max| run(max, &args)),
+    };
+
+    #[cfg(not(feature = "rppal"))]
+    let result = Max6675::new(&args.device).and_then(|max| run(max, &args));
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("max6675: {e}");
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_hal::spi::ErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn raw_shows_every_word() {
+        // open, impossible, and all ones
+        for raw in [0x0324, 0x0152, 0xFFFF] {
+            assert_eq!(Unit::Raw.format(raw), Ok(format!("{raw:#06x}")));
+        }
+    }
+
+    #[test]
+    fn temperatures_need_a_good_frame() {
+        assert_eq!(Unit::Celsius.format(0x0320), Ok("25".into()));
+        assert_eq!(Unit::Fahrenheit.format(0x0320), Ok("77".into()));
+        assert_eq!(Unit::Kelvin.format(0x0320), Ok("298.15".into()));
+
+        assert_eq!(Unit::Celsius.format(0x0324), Err(Error::OpenCircuit));
+        assert_eq!(
+            Unit::Celsius.format(0xFFFF),
+            Err(Error::InvalidFrame { raw: 0xFFFF })
+        );
+    }
+
+    #[test]
+    fn exit_codes_tell_errors_apart() {
+        let cases = [
+            (Error::OpenCircuit, exit::OPEN_CIRCUIT),
+            (Error::ReceivedNothing, exit::RECEIVED_NOTHING),
+            (
+                Error::SPI {
+                    kind: ErrorKind::Other,
+                    message: "oops".into(),
+                },
+                exit::SPI,
+            ),
+            (
+                Error::DeviceNotPresent { raw: 0xFFFF },
+                exit::DEVICE_NOT_PRESENT,
+            ),
+            (Error::InvalidFrame { raw: 0xFFFF }, exit::OTHER),
+            (
+                Error::InvalidPath {
+                    path: "/dev/null".into(),
+                },
+                exit::OTHER,
+            ),
+        ];
+
+        for (e, code) in cases {
+            assert_eq!(exit_code(&e), code, "{e}");
+        }
+        assert_eq!(
+            [
+                exit::OTHER,
+                exit::OPEN_CIRCUIT,
+                exit::RECEIVED_NOTHING,
+                exit::SPI,
+                exit::DEVICE_NOT_PRESENT
+            ],
+            [1, 2, 3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn bad_arguments_exit_with_1() {
+        assert_eq!(parse_args(["max6675", "--nope"]).unwrap_err(), exit::OTHER);
+        assert_eq!(
+            parse_args(["max6675", "--interval", "-1"]).unwrap_err(),
+            exit::OTHER
+        );
+        assert_eq!(
+            parse_args(["max6675", "--count", "3"]).unwrap_err(),
+            exit::OTHER
+        );
+
+        // asking for help isn't a failure
+        assert_eq!(parse_args(["max6675", "--help"]).unwrap_err(), 0);
+
+        let args = parse_args(["max6675", "--unit", "raw", "-i", "0.5"]).unwrap();
+        assert!(matches!(args.unit, Unit::Raw));
+        assert_eq!(args.interval, Some(Duration::from_millis(500)));
+    }
+}
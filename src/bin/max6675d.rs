@@ -0,0 +1,35 @@
+//! # max6675d
+//!
+//! Polls MAX6675s and logs their readings, forever.
+//!
+//! ```text
+//! $ max6675d /etc/max6675d.toml
+//! ```
+//!
+//! See `linux_max6675::daemon::Config` for what goes in the config file.
+
+use std::process::ExitCode;
+
+use linux_max6675::daemon::Daemon;
+
+const DEFAULT_CONFIG: &str = "/etc/max6675d.toml";
+
+fn main() -> ExitCode {
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_CONFIG.to_string());
+
+    let mut daemon = match Daemon::from_config_file(&path) {
+        Ok(daemon) => daemon,
+        Err(e) => {
+            eprintln!("max6675d: {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // sinks are numbered from 1, in the order they're in the config
+    daemon.run(|sink, e| eprintln!("max6675d: couldn't write to sink {}: {e}", sink + 1));
+
+    eprintln!("max6675d: no sensors to poll in {path}");
+    ExitCode::FAILURE
+}
@@ -0,0 +1,400 @@
+//! # calibration
+//!
+//! Corrections for probes that don't quite read true.
+//!
+//! A [`Calibration`] maps what the MAX6675 says to what the temperature
+//! actually is. It's made of a multi-point correction table (say, from
+//! checking the probe against a reference bath), followed by a linear gain and
+//! offset. Attach one to a driver with
+//! [`Max6675::set_calibration`](crate::Max6675::set_calibration) and
+//! `read_celsius` gives you corrected values.
+//!
+//! With the `serde` feature, calibrations can be saved as JSON, and with the
+//! `toml` feature, as TOML, so they can travel with their probes.
+//!
+//! ## Example
+//!
+//! ```no_run
+//!
+//! use linux_max6675::{Calibration, Max6675};
+//!
+//! let mut max = Max6675::new("/dev/spidev0.0").unwrap();
+//! max.set_calibration(Some(
+//!     Calibration::new()
+//!         .with_point(0.0, 0.5)
+//!         .with_point(100.0, 99.25)
+//!         .with_offset(-0.1),
+//! ));
+//!
+//! println!("{}° C, corrected", max.read_celsius().unwrap());
+//!
+//! ```
+
+#[cfg(feature = "serde")]
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use thiserror::Error;
+
+/// One point in a correction table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point {
+    /// What the MAX6675 read, in ° C.
+    pub measured: f64,
+    /// What the temperature really was, in ° C.
+    pub actual: f64,
+}
+
+/// A correction from measured to actual temperature.
+///
+/// Corrections happen in this order:
+///
+/// 1. The table of points, if there are any. Between two points, we
+///    interpolate linearly. Past either end, we keep going along the nearest
+///    segment. A table with just one point shifts everything by the same
+///    amount.
+/// 2. The gain, which multiplies.
+/// 3. The offset, which adds.
+///
+/// A saved calibration looks like this in TOML:
+///
+/// ```toml
+/// offset = -0.1
+/// gain = 1.0
+///
+/// [[point]]
+/// measured = 0.0
+/// actual = 0.5
+///
+/// [[point]]
+/// measured = 100.0
+/// actual = 99.25
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "File", into = "File"))]
+pub struct Calibration {
+    offset: f64,
+    gain: f64,
+    /// Sorted by `measured`.
+    points: Vec<Point>,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Calibration {
+    /// A calibration that doesn't change anything.
+    pub fn new() -> Self {
+        Self {
+            offset: 0.0,
+            gain: 1.0,
+            points: Vec::new(),
+        }
+    }
+
+    /// Adds `offset` ° C to every reading.
+    pub fn with_offset(mut self, offset: f64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Multiplies every reading by `gain`.
+    pub fn with_gain(mut self, gain: f64) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Adds a point to the correction table: when the MAX6675 reads
+    /// `measured`, it's really `actual`.
+    pub fn with_point(mut self, measured: f64, actual: f64) -> Self {
+        self.points.push(Point { measured, actual });
+        self.points
+            .sort_by(|a, b| a.measured.total_cmp(&b.measured));
+        self
+    }
+
+    /// The offset, in ° C.
+    pub fn offset(&self) -> f64 {
+        self.offset
+    }
+
+    /// The gain.
+    pub fn gain(&self) -> f64 {
+        self.gain
+    }
+
+    /// The correction table, sorted by measured temperature.
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    /// Corrects a temperature in ° C.
+    pub fn apply(&self, celsius: f64) -> f64 {
+        self.gain * self.table(celsius) + self.offset
+    }
+
+    /// Looks a temperature up in the correction table.
+    fn table(&self, celsius: f64) -> f64 {
+        match self.points.as_slice() {
+            [] => celsius,
+            [only] => celsius + (only.actual - only.measured),
+            points => {
+                // find the segment we're in, or the nearest one at either end
+                let after = points
+                    .iter()
+                    .position(|p| p.measured > celsius)
+                    .unwrap_or(points.len() - 1)
+                    .clamp(1, points.len() - 1);
+                let (a, b) = (points[after - 1], points[after]);
+
+                // points measured at the same temperature can't be
+                // interpolated between, so just take the first
+                if a.measured == b.measured {
+                    return celsius + (a.actual - a.measured);
+                }
+
+                let t = (celsius - a.measured) / (b.measured - a.measured);
+                a.actual + t * (b.actual - a.actual)
+            }
+        }
+    }
+}
+
+/// An error from loading or saving a [`Calibration`].
+#[cfg(feature = "serde")]
+#[derive(Debug, Error)]
+pub enum CalibrationError {
+    #[error("Couldn't read or write the calibration file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("The calibration isn't valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[cfg(feature = "toml")]
+    #[error("The calibration isn't valid TOML: {0}")]
+    TomlDe(#[from] toml::de::Error),
+    #[cfg(feature = "toml")]
+    #[error("Couldn't write the calibration as TOML: {0}")]
+    TomlSer(#[from] toml::ser::Error),
+    #[error("`{path}` should end in `.json` or `.toml`, so we know what format it's in.")]
+    UnknownFormat { path: String },
+}
+
+/// How a calibration looks on disk.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    #[serde(default)]
+    offset: f64,
+    #[serde(default = "File::default_gain")]
+    gain: f64,
+    #[serde(rename = "point", default)]
+    points: Vec<Point>,
+}
+
+#[cfg(feature = "serde")]
+impl File {
+    fn default_gain() -> f64 {
+        1.0
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<File> for Calibration {
+    fn from(file: File) -> Self {
+        file.points.into_iter().fold(
+            Calibration::new()
+                .with_offset(file.offset)
+                .with_gain(file.gain),
+            |calibration, p| calibration.with_point(p.measured, p.actual),
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Calibration> for File {
+    fn from(calibration: Calibration) -> Self {
+        Self {
+            offset: calibration.offset,
+            gain: calibration.gain,
+            points: calibration.points,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Calibration {
+    /// Loads a calibration from JSON.
+    pub fn from_json(json: &str) -> Result<Self, CalibrationError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Saves the calibration as pretty JSON.
+    pub fn to_json(&self) -> String {
+        // a `Calibration` is always valid JSON
+        serde_json::to_string_pretty(self).expect("calibrations should serialize")
+    }
+
+    /// Loads a calibration from TOML.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Self, CalibrationError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Saves the calibration as TOML.
+    #[cfg(feature = "toml")]
+    pub fn to_toml(&self) -> Result<String, CalibrationError> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// Loads a calibration file. Its extension says what format it's in:
+    /// `.json`, or `.toml` with the `toml` feature.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CalibrationError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json(&contents),
+            #[cfg(feature = "toml")]
+            Some("toml") => Self::from_toml(&contents),
+            _ => Err(CalibrationError::UnknownFormat {
+                path: path.display().to_string(),
+            }),
+        }
+    }
+
+    /// Saves the calibration to a file, in the format its extension says.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CalibrationError> {
+        let path = path.as_ref();
+
+        let contents = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => self.to_json(),
+            #[cfg(feature = "toml")]
+            Some("toml") => self.to_toml()?,
+            _ => {
+                return Err(CalibrationError::UnknownFormat {
+                    path: path.display().to_string(),
+                })
+            }
+        };
+
+        Ok(std::fs::write(path, contents)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{backend::mock::MockMax6675, Max6675};
+
+    #[test]
+    fn does_nothing_by_default() {
+        assert_eq!(Calibration::new().apply(123.25), 123.25);
+    }
+
+    #[test]
+    fn applies_gain_then_offset() {
+        let calibration = Calibration::new().with_gain(1.5).with_offset(-2.0);
+        assert_eq!(calibration.apply(10.0), 13.0);
+    }
+
+    #[test]
+    fn interpolates_the_table() {
+        let calibration = Calibration::new()
+            .with_point(100.0, 98.0)
+            .with_point(0.0, 1.0)
+            .with_point(200.0, 200.0);
+
+        // the points get sorted
+        assert_eq!(calibration.points()[0].measured, 0.0);
+
+        assert_eq!(calibration.apply(0.0), 1.0);
+        assert_eq!(calibration.apply(50.0), 49.5);
+        assert_eq!(calibration.apply(100.0), 98.0);
+        assert_eq!(calibration.apply(150.0), 149.0);
+
+        // past the ends, we keep going along the end segments
+        assert_eq!(calibration.apply(-100.0), -96.0);
+        assert_eq!(calibration.apply(300.0), 302.0);
+    }
+
+    #[test]
+    fn one_point_is_a_shift() {
+        let calibration = Calibration::new().with_point(50.0, 51.5);
+        assert_eq!(calibration.apply(400.0), 401.5);
+    }
+
+    #[test]
+    fn corrects_the_driver() {
+        let mut spi = MockMax6675::new();
+        spi.push_profile([100.0, 100.0, 100.0]);
+        let mut max = Max6675::from_spi(spi);
+        max.set_conversion_time(Duration::ZERO);
+
+        max.set_calibration(Some(Calibration::new().with_offset(-1.0)));
+        assert_eq!(max.read_celsius(), Ok(99.0));
+        assert_eq!(max.read_kelvin(), Ok(372.15));
+        assert_eq!(max.read_fahrenheit(), Ok(210.2));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trips_through_files() {
+        let calibration = Calibration::new()
+            .with_gain(1.01)
+            .with_point(0.0, 0.5)
+            .with_point(100.0, 99.25);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut formats = vec!["json"];
+        if cfg!(feature = "toml") {
+            formats.push("toml");
+        }
+
+        for format in formats {
+            let path = dir.path().join(format!("probe.{format}"));
+            calibration.save(&path).unwrap();
+            assert_eq!(Calibration::load(&path).unwrap(), calibration);
+        }
+
+        assert!(matches!(
+            calibration.save(dir.path().join("probe.yaml")),
+            Err(CalibrationError::UnknownFormat { .. })
+        ));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn loads_hand_written_toml() {
+        let calibration = Calibration::from_toml(
+            r#"
+            offset = -0.1
+
+            [[point]]
+            measured = 100.0
+            actual = 99.25
+
+            [[point]]
+            measured = 0.0
+            actual = 0.5
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(calibration.gain(), 1.0);
+        assert_eq!(
+            calibration.points()[0],
+            Point {
+                measured: 0.0,
+                actual: 0.5
+            }
+        );
+        assert!(Calibration::from_toml("ofset = 1.0").is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! # max31855
+//!
+//! The MAX31855: the MAX6675's bigger sibling.
+//!
+//! It sends 32-bit frames (see the MAX31855 datasheet):
+//!
+//! - D31-D18: the thermocouple temperature, signed, in quarter degrees.
+//! - D17: reserved.
+//! - D16: high if there's any fault.
+//! - D15-D4: the chip's own temperature, signed, in sixteenths of a degree.
+//! - D3: reserved.
+//! - D2: the thermocouple is shorted to VCC.
+//! - D1: the thermocouple is shorted to ground.
+//! - D0: the thermocouple is open.
+//!
+//! Unlike the MAX6675, it reads below zero, and it converts continuously, so
+//! reading doesn't start a new conversion.
+//!
+//! ## Example
+//!
+//! ```no_run
+//!
+//! use linux_max6675::{converter::max31855::Max31855, ThermocoupleConverter};
+//!
+//! let mut max = Max31855::new("/dev/spidev0.0").unwrap();
+//! let conversion = max.read_conversion().unwrap();
+//!
+//! println!(
+//!     "it's {}° C out there, and {}° C on the board",
+//!     conversion.thermocouple,
+//!     conversion.cold_junction.unwrap(),
+//! );
+//!
+//! ```
+
+use std::{ops::RangeInclusive, time::Duration};
+
+use embedded_hal::spi::SpiDevice;
+
+use super::{Conversion, Fault, Pacer, ThermocoupleConverter};
+use crate::{Error, Sensor};
+
+/// How long the MAX31855 takes to finish a conversion, at most.
+pub const CONVERSION_TIME: Duration = Duration::from_millis(100);
+
+/// The SPI clock speed used when opening a MAX31855. It tops out at 5 MHz.
+pub const CLOCK_SPEED: u32 = 1_000_000;
+
+/// A MAX31855 connected over SPI.
+///
+/// The chip keeps converting in the background, so there's no point reading
+/// it more often than every [`CONVERSION_TIME`]. This waits until then.
+#[derive(Debug)]
+pub struct Max31855<SPI> {
+    spi: SPI,
+    pacer: Pacer,
+}
+
+#[cfg(feature = "spidev")]
+impl Max31855<crate::Spidev> {
+    /// Opens the MAX31855 at the given spidev path, like `/dev/spidev0.0`.
+    ///
+    /// The SPI is configured for the chip: mode 0 (CPOL = 0, CPHA = 0) at
+    /// [`CLOCK_SPEED`].
+    pub fn new(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        let mut spi = crate::Spidev::open(path)?;
+        spi.set_mode(embedded_hal::spi::MODE_0)?;
+        spi.set_speed(CLOCK_SPEED)?;
+        Ok(Self::from_spi(spi))
+    }
+}
+
+impl<SPI> Max31855<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    /// Wraps any SPI device that you've already configured yourself.
+    ///
+    /// Make sure it's in mode 0 and clocked at 5 MHz or below!
+    pub fn from_spi(spi: SPI) -> Self {
+        Self {
+            spi,
+            pacer: Pacer::new(CONVERSION_TIME),
+        }
+    }
+
+    /// Gives back the underlying SPI device.
+    pub fn into_inner(self) -> SPI {
+        self.spi
+    }
+
+    /// Changes how long we wait between reads. Defaults to
+    /// [`CONVERSION_TIME`].
+    pub fn set_conversion_time(&mut self, conversion_time: Duration) {
+        self.pacer.conversion_time = conversion_time;
+    }
+
+    /// Tries to return the raw 32-bit frame.
+    pub fn read_raw(&mut self) -> Result<u32, Error> {
+        self.pacer.wait();
+        let bytes = read(&mut self.spi)?;
+        self.pacer.started();
+        Ok(bytes)
+    }
+
+    /// Tries to read the thermocouple and the chip's own temperature.
+    pub fn read_conversion(&mut self) -> Result<Conversion, Error> {
+        let bytes = self.read_raw()?;
+
+        if let Some(e) = fault(bytes) {
+            return Err(e);
+        }
+
+        Ok(Conversion {
+            thermocouple: parse_celsius(bytes),
+            cold_junction: Some(parse_cold_junction(bytes)),
+        })
+    }
+
+    /// Tries to read the thermocouple's temperature in Celsius.
+    pub fn read_celsius(&mut self) -> Result<f64, Error> {
+        Ok(self.read_conversion()?.thermocouple)
+    }
+}
+
+impl<SPI> Sensor for Max31855<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    fn read_celsius(&mut self) -> Result<f64, Error> {
+        Max31855::read_celsius(self)
+    }
+}
+
+impl<SPI> ThermocoupleConverter for Max31855<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    fn part(&self) -> &'static str {
+        "MAX31855"
+    }
+
+    fn range(&self) -> RangeInclusive<f64> {
+        -270.0..=1800.0
+    }
+
+    fn resolution(&self) -> f64 {
+        0.25
+    }
+
+    fn read_conversion(&mut self) -> Result<Conversion, Error> {
+        Max31855::read_conversion(self)
+    }
+}
+
+/// Tries to read a raw 32-bit frame from a MAX31855.
+///
+/// Only fails if there's something wrong with the SPI connection.
+pub fn read<SPI>(spi: &mut SPI) -> Result<u32, Error>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    let mut buf = [0_u8; 4];
+    spi.read(&mut buf).map_err(Error::from_spi)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+/// Check if the thermocouple is open (bit D0 is high).
+pub fn is_open(bytes: u32) -> bool {
+    (bytes & 0x01) != 0
+}
+
+/// What's wrong with the thermocouple, if the frame says anything is.
+///
+/// An open circuit wins over shorts, since a short can't be seen without a
+/// thermocouple.
+pub fn fault(bytes: u32) -> Option<Error> {
+    if bytes & 0x0001_0000 == 0 {
+        return None;
+    }
+
+    Some(if is_open(bytes) {
+        Error::OpenCircuit
+    } else if bytes & 0x04 != 0 {
+        Error::Fault {
+            fault: Fault::ShortToVcc,
+        }
+    } else {
+        Error::Fault {
+            fault: Fault::ShortToGround,
+        }
+    })
+}
+
+/// Parse the thermocouple's temperature from a frame, in Celsius.
+///
+/// Extracts the signed 14-bit number of quarter degrees from D31-D18.
+pub fn parse_celsius(bytes: u32) -> f64 {
+    f64::from((bytes as i32) >> 18) * 0.25
+}
+
+/// Parse the chip's own temperature from a frame, in Celsius.
+///
+/// Extracts the signed 12-bit number of sixteenth degrees from D15-D4.
+pub fn parse_cold_junction(bytes: u32) -> f64 {
+    f64::from(((bytes as i32) << 16) >> 20) * 0.0625
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockMax6675;
+
+    // examples from the MAX31855 datasheet's temperature tables
+    #[test]
+    fn parses_temperatures() {
+        assert_eq!(parse_celsius(0x6400_0000), 1600.0);
+        assert_eq!(parse_celsius(0x0190_0000), 25.0);
+        assert_eq!(parse_celsius(0x0000_0000), 0.0);
+        assert_eq!(parse_celsius(0xFFFC_0000), -0.25);
+        assert_eq!(parse_celsius(0xF060_0000), -250.0);
+
+        assert_eq!(parse_cold_junction(0x0000_7F00), 127.0);
+        assert_eq!(parse_cold_junction(0x0000_1910), 25.0625);
+        assert_eq!(parse_cold_junction(0x0000_FFF0), -0.0625);
+        assert_eq!(parse_cold_junction(0x0000_C900), -55.0);
+    }
+
+    #[test]
+    fn reads_faults() {
+        assert_eq!(fault(0x0064_1900), None);
+        assert_eq!(fault(0x0001_0001), Some(Error::OpenCircuit));
+        assert_eq!(
+            fault(0x0001_0002),
+            Some(Error::Fault {
+                fault: Fault::ShortToGround
+            })
+        );
+        assert_eq!(
+            fault(0x0001_0004),
+            Some(Error::Fault {
+                fault: Fault::ShortToVcc
+            })
+        );
+    }
+
+    #[test]
+    fn reads_the_chip() {
+        let mut mock = MockMax6675::new();
+        mock.push_bytes([0xF0, 0x60, 0x19, 0x10])
+            .push_bytes([0x00, 0x01, 0x19, 0x12]);
+        let mut max = Max31855::from_spi(mock);
+        max.set_conversion_time(Duration::ZERO);
+
+        assert_eq!(
+            max.read_conversion(),
+            Ok(Conversion {
+                thermocouple: -250.0,
+                cold_junction: Some(25.0625),
+            })
+        );
+        assert_eq!(
+            max.read_celsius(),
+            Err(Error::Fault {
+                fault: Fault::ShortToGround
+            })
+        );
+    }
+}
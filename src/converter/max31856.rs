@@ -0,0 +1,315 @@
+//! # max31856
+//!
+//! The MAX31856: a register-based converter that works with any common
+//! thermocouple type.
+//!
+//! Instead of a fixed frame, the MAX31856 has registers (see the MAX31856
+//! datasheet). We put it in automatic conversion mode with open-circuit
+//! detection on, then read these in one go:
+//!
+//! - `CJTH`/`CJTL` (0x0A-0x0B): the chip's own temperature, signed 14 bits,
+//!   in 64ths of a degree.
+//! - `LTCBH`/`LTCBM`/`LTCBL` (0x0C-0x0E): the linearized thermocouple
+//!   temperature, signed 19 bits, in 128ths of a degree.
+//! - `SR` (0x0F): the fault status.
+//!
+//! ## Example
+//!
+//! ```no_run
+//!
+//! use linux_max6675::converter::max31856::{Max31856, ThermocoupleType};
+//!
+//! let mut max = Max31856::new("/dev/spidev0.0", ThermocoupleType::J).unwrap();
+//! println!("it's {}° C in here!", max.read_celsius().unwrap());
+//!
+//! ```
+
+use std::ops::RangeInclusive;
+
+use embedded_hal::spi::{Operation, SpiDevice};
+
+use super::{Conversion, Fault, ThermocoupleConverter};
+use crate::{Error, Sensor};
+
+/// The SPI clock speed used when opening a MAX31856. It tops out at 5 MHz.
+pub const CLOCK_SPEED: u32 = 1_000_000;
+
+/// Configuration register 0.
+const CR0: u8 = 0x00;
+/// Configuration register 1.
+const CR1: u8 = 0x01;
+/// The first register we read: the cold junction's high byte.
+const CJTH: u8 = 0x0A;
+
+/// `CR0`: convert automatically (`CMODE`), and detect open circuits
+/// (`OCFAULT` = 01).
+const CR0_AUTO_OPEN_DETECT: u8 = 0x90;
+
+/// Setting the top bit of an address writes to it.
+const WRITE: u8 = 0x80;
+
+/// Which kind of thermocouple is connected to a [`Max31856`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ThermocoupleType {
+    B,
+    E,
+    J,
+    #[default]
+    K,
+    N,
+    R,
+    S,
+    T,
+}
+
+impl ThermocoupleType {
+    /// What goes in `CR1`'s `TC TYPE` bits.
+    fn bits(self) -> u8 {
+        match self {
+            ThermocoupleType::B => 0,
+            ThermocoupleType::E => 1,
+            ThermocoupleType::J => 2,
+            ThermocoupleType::K => 3,
+            ThermocoupleType::N => 4,
+            ThermocoupleType::R => 5,
+            ThermocoupleType::S => 6,
+            ThermocoupleType::T => 7,
+        }
+    }
+
+    /// The temperatures the MAX31856 can measure with this type, in ° C.
+    pub fn range(self) -> RangeInclusive<f64> {
+        match self {
+            ThermocoupleType::B => 250.0..=1820.0,
+            ThermocoupleType::E => -200.0..=1000.0,
+            ThermocoupleType::J => -210.0..=1200.0,
+            ThermocoupleType::K => -200.0..=1372.0,
+            ThermocoupleType::N => -200.0..=1300.0,
+            ThermocoupleType::R | ThermocoupleType::S => -50.0..=1768.0,
+            ThermocoupleType::T => -200.0..=400.0,
+        }
+    }
+}
+
+/// A MAX31856 connected over SPI.
+///
+/// The chip converts continuously (about every 100 ms), so reads never wait.
+/// Reading faster than that just gives you the same conversion again.
+#[derive(Debug)]
+pub struct Max31856<SPI> {
+    spi: SPI,
+    thermocouple: ThermocoupleType,
+}
+
+#[cfg(feature = "spidev")]
+impl Max31856<crate::Spidev> {
+    /// Opens the MAX31856 at the given spidev path, like `/dev/spidev0.0`,
+    /// and sets it up for `thermocouple`.
+    ///
+    /// The SPI is configured for the chip: mode 1 (CPOL = 0, CPHA = 1) at
+    /// [`CLOCK_SPEED`].
+    pub fn new(
+        path: impl AsRef<std::path::Path>,
+        thermocouple: ThermocoupleType,
+    ) -> Result<Self, Error> {
+        let mut spi = crate::Spidev::open(path)?;
+        spi.set_speed(CLOCK_SPEED)?;
+        Self::from_spi(spi, thermocouple)
+    }
+}
+
+impl<SPI> Max31856<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    /// Wraps any SPI device that you've already configured yourself, and sets
+    /// the chip up for `thermocouple`.
+    ///
+    /// Make sure it's in mode 1 or 3 and clocked at 5 MHz or below!
+    pub fn from_spi(spi: SPI, thermocouple: ThermocoupleType) -> Result<Self, Error> {
+        let mut max = Self { spi, thermocouple };
+        max.set_thermocouple_type(thermocouple)?;
+        Ok(max)
+    }
+
+    /// Gives back the underlying SPI device.
+    pub fn into_inner(self) -> SPI {
+        self.spi
+    }
+
+    /// Which kind of thermocouple the chip is set up for.
+    pub fn thermocouple_type(&self) -> ThermocoupleType {
+        self.thermocouple
+    }
+
+    /// Sets the chip up for another kind of thermocouple.
+    pub fn set_thermocouple_type(&mut self, thermocouple: ThermocoupleType) -> Result<(), Error> {
+        self.write_register(CR0, CR0_AUTO_OPEN_DETECT)?;
+        self.write_register(CR1, thermocouple.bits())?;
+        self.thermocouple = thermocouple;
+        Ok(())
+    }
+
+    /// Writes one register.
+    fn write_register(&mut self, address: u8, value: u8) -> Result<(), Error> {
+        self.spi
+            .write(&[address | WRITE, value])
+            .map_err(Error::from_spi)
+    }
+
+    /// Tries to return the raw registers, from `CJTH` to `SR`.
+    pub fn read_raw(&mut self) -> Result<[u8; 6], Error> {
+        let mut registers = [0_u8; 6];
+        self.spi
+            .transaction(&mut [Operation::Write(&[CJTH]), Operation::Read(&mut registers)])
+            .map_err(Error::from_spi)?;
+        Ok(registers)
+    }
+
+    /// Tries to read the thermocouple and the chip's own temperature.
+    pub fn read_conversion(&mut self) -> Result<Conversion, Error> {
+        let [cjth, cjtl, ltcbh, ltcbm, ltcbl, sr] = self.read_raw()?;
+
+        if let Some(e) = fault(sr) {
+            return Err(e);
+        }
+
+        Ok(Conversion {
+            thermocouple: parse_celsius([ltcbh, ltcbm, ltcbl]),
+            cold_junction: Some(parse_cold_junction([cjth, cjtl])),
+        })
+    }
+
+    /// Tries to read the thermocouple's temperature in Celsius.
+    pub fn read_celsius(&mut self) -> Result<f64, Error> {
+        Ok(self.read_conversion()?.thermocouple)
+    }
+}
+
+impl<SPI> Sensor for Max31856<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    fn read_celsius(&mut self) -> Result<f64, Error> {
+        Max31856::read_celsius(self)
+    }
+}
+
+impl<SPI> ThermocoupleConverter for Max31856<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    fn part(&self) -> &'static str {
+        "MAX31856"
+    }
+
+    fn range(&self) -> RangeInclusive<f64> {
+        self.thermocouple.range()
+    }
+
+    fn resolution(&self) -> f64 {
+        0.0078125
+    }
+
+    fn read_conversion(&mut self) -> Result<Conversion, Error> {
+        Max31856::read_conversion(self)
+    }
+}
+
+/// What's wrong with the thermocouple, according to the fault status
+/// register (`SR`).
+///
+/// If more than one fault is set, an open circuit wins, then the rest in the
+/// register's order.
+pub fn fault(sr: u8) -> Option<Error> {
+    if sr & 0x01 != 0 {
+        return Some(Error::OpenCircuit);
+    }
+
+    [
+        (0x80, Fault::ColdJunctionRange),
+        (0x40, Fault::ThermocoupleRange),
+        (0x20, Fault::ColdJunctionHigh),
+        (0x10, Fault::ColdJunctionLow),
+        (0x08, Fault::ThermocoupleHigh),
+        (0x04, Fault::ThermocoupleLow),
+        (0x02, Fault::OverUnderVoltage),
+    ]
+    .into_iter()
+    .find(|(bit, _)| sr & bit != 0)
+    .map(|(_, fault)| Error::Fault { fault })
+}
+
+/// Parse the thermocouple's temperature from `LTCBH`, `LTCBM` and `LTCBL`,
+/// in Celsius.
+pub fn parse_celsius([h, m, l]: [u8; 3]) -> f64 {
+    // the 19 bits are at the top of the 24, so push them to the top of an
+    // i32 and shift back down to keep the sign
+    f64::from(i32::from_be_bytes([h, m, l, 0]) >> 13) * 0.0078125
+}
+
+/// Parse the chip's own temperature from `CJTH` and `CJTL`, in Celsius.
+pub fn parse_cold_junction([h, l]: [u8; 2]) -> f64 {
+    f64::from(i16::from_be_bytes([h, l]) >> 2) * 0.015625
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockMax6675;
+
+    // examples from the MAX31856 datasheet's temperature tables
+    #[test]
+    fn parses_temperatures() {
+        assert_eq!(parse_celsius([0x64, 0x00, 0x00]), 1600.0);
+        assert_eq!(parse_celsius([0x01, 0x90, 0x00]), 25.0);
+        assert_eq!(parse_celsius([0x00, 0x00, 0x20]), 0.0078125);
+        assert_eq!(parse_celsius([0xFF, 0xFF, 0xE0]), -0.0078125);
+        assert_eq!(parse_celsius([0xF0, 0x60, 0x00]), -250.0);
+
+        assert_eq!(parse_cold_junction([0x7F, 0x00]), 127.0);
+        assert_eq!(parse_cold_junction([0x19, 0x04]), 25.015625);
+        assert_eq!(parse_cold_junction([0xFF, 0xFC]), -0.015625);
+        assert_eq!(parse_cold_junction([0xC9, 0x00]), -55.0);
+    }
+
+    #[test]
+    fn reads_faults() {
+        assert_eq!(fault(0x00), None);
+        assert_eq!(fault(0x03), Some(Error::OpenCircuit));
+        assert_eq!(
+            fault(0x42),
+            Some(Error::Fault {
+                fault: Fault::ThermocoupleRange
+            })
+        );
+    }
+
+    #[test]
+    fn configures_and_reads_the_chip() {
+        let mut mock = MockMax6675::new();
+        mock.push_bytes([0x19, 0x00, 0x01, 0x90, 0x00, 0x00])
+            .push_bytes([0x19, 0x00, 0x00, 0x00, 0x00, 0x01]);
+
+        let mut max = Max31856::from_spi(mock, ThermocoupleType::J).unwrap();
+        assert_eq!(max.range(), -210.0..=1200.0);
+        assert_eq!(
+            max.read_conversion(),
+            Ok(Conversion {
+                thermocouple: 25.0,
+                cold_junction: Some(25.0),
+            })
+        );
+        assert_eq!(max.read_celsius(), Err(Error::OpenCircuit));
+
+        let mock = max.into_inner();
+        assert_eq!(
+            mock.written(),
+            [vec![0x80, 0x90], vec![0x81, 0x02], vec![0x0A], vec![0x0A]]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! # max6674
+//!
+//! The MAX6674: a 10-bit MAX6675 for lower temperatures.
+//!
+//! It speaks the same 16-bit frames as the MAX6675, with the bits shuffled a
+//! little (see the MAX6674 datasheet):
+//!
+//! - D15: dummy sign bit, always 0.
+//! - D14-D5: the temperature, in eighths of a degree.
+//! - D4: high when the thermocouple is open.
+//! - D3: device ID, always 0.
+//! - D2-D0: three-state.
+//!
+//! ## Example
+//!
+//! ```no_run
+//!
+//! use linux_max6675::converter::max6674::Max6674;
+//!
+//! let mut max = Max6674::new("/dev/spidev0.0").unwrap();
+//! println!("it's {}° C in here!", max.read_celsius().unwrap());
+//!
+//! ```
+
+use std::{ops::RangeInclusive, time::Duration};
+
+use embedded_hal::spi::SpiDevice;
+
+use super::{Conversion, Pacer, ThermocoupleConverter};
+use crate::{Error, Sensor};
+
+/// How long the MAX6674 needs to finish a conversion.
+pub const CONVERSION_TIME: Duration = Duration::from_millis(220);
+
+/// A MAX6674 connected over SPI.
+///
+/// Like [`Max6675`](crate::Max6675), it waits out the chip's conversion time
+/// between reads.
+#[derive(Debug)]
+pub struct Max6674<SPI> {
+    spi: SPI,
+    pacer: Pacer,
+}
+
+#[cfg(feature = "spidev")]
+impl Max6674<crate::Spidev> {
+    /// Opens the MAX6674 at the given spidev path, like `/dev/spidev0.0`.
+    ///
+    /// It's wired just like a MAX6675, so the SPI is set up the same way.
+    pub fn new(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        Ok(Self::from_spi(crate::Spidev::open(path)?))
+    }
+}
+
+impl<SPI> Max6674<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    /// Wraps any SPI device that you've already configured yourself.
+    ///
+    /// Make sure it's in mode 1 and clocked at 4.3 MHz or below!
+    pub fn from_spi(spi: SPI) -> Self {
+        Self {
+            spi,
+            pacer: Pacer::new(CONVERSION_TIME),
+        }
+    }
+
+    /// Gives back the underlying SPI device.
+    pub fn into_inner(self) -> SPI {
+        self.spi
+    }
+
+    /// Changes how long we wait for a conversion. Defaults to
+    /// [`CONVERSION_TIME`].
+    pub fn set_conversion_time(&mut self, conversion_time: Duration) {
+        self.pacer.conversion_time = conversion_time;
+    }
+
+    /// Tries to return the raw frame, once the last conversion is done.
+    pub fn read_raw(&mut self) -> Result<u16, Error> {
+        self.pacer.wait();
+        let bytes = read(&mut self.spi)?;
+        self.pacer.started();
+        Ok(bytes)
+    }
+
+    /// Tries to read the thermocouple's temperature in Celsius.
+    pub fn read_celsius(&mut self) -> Result<f64, Error> {
+        let bytes = self.read_raw()?;
+
+        // a real chip never sets the sign or device ID bits
+        if bytes & 0x8008 != 0 {
+            return Err(Error::InvalidFrame { raw: bytes });
+        }
+        if is_open(bytes) {
+            return Err(Error::OpenCircuit);
+        }
+
+        Ok(parse_celsius(bytes))
+    }
+}
+
+impl<SPI> Sensor for Max6674<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    fn read_celsius(&mut self) -> Result<f64, Error> {
+        Max6674::read_celsius(self)
+    }
+}
+
+impl<SPI> ThermocoupleConverter for Max6674<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    fn part(&self) -> &'static str {
+        "MAX6674"
+    }
+
+    fn range(&self) -> RangeInclusive<f64> {
+        0.0..=127.875
+    }
+
+    fn resolution(&self) -> f64 {
+        0.125
+    }
+
+    fn read_conversion(&mut self) -> Result<Conversion, Error> {
+        Ok(Conversion {
+            thermocouple: Max6674::read_celsius(self)?,
+            cold_junction: None,
+        })
+    }
+}
+
+/// Tries to read a raw frame from a MAX6674. This is the same as
+/// [`crate::read`], since both chips send 16 bits.
+pub fn read<SPI>(spi: &mut SPI) -> Result<u16, Error>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    crate::read(spi)
+}
+
+/// Check if the thermocouple is open (bit D4 is high).
+pub fn is_open(bytes: u16) -> bool {
+    (bytes & 0x10) != 0
+}
+
+/// Parse the temperature from a frame, in Celsius.
+///
+/// Extracts the 10-bit number of eighth degrees from D14-D5.
+pub fn parse_celsius(bytes: u16) -> f64 {
+    f64::from((bytes >> 5) & 0x03FF) * 0.125
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockMax6675;
+
+    #[test]
+    fn parses_celsius() {
+        assert_eq!(parse_celsius(0x0000), 0.0);
+        assert_eq!(parse_celsius(1 << 5), 0.125);
+        assert_eq!(parse_celsius(0x7FE0), 127.875);
+        // the open and three-state bits don't leak in
+        assert_eq!(parse_celsius(0x7FF7), 127.875);
+    }
+
+    #[test]
+    fn reads_the_chip() {
+        let mut mock = MockMax6675::new();
+        mock.push_frame(200 << 5)
+            .push_frame(0x0010)
+            .push_frame(0x0008)
+            .push_frame(0xFFFF);
+        let mut max = Max6674::from_spi(mock);
+        max.set_conversion_time(Duration::ZERO);
+
+        assert_eq!(max.read_celsius(), Ok(25.0));
+        assert_eq!(max.read_celsius(), Err(Error::OpenCircuit));
+        assert_eq!(max.read_celsius(), Err(Error::InvalidFrame { raw: 0x0008 }));
+        assert_eq!(max.read_celsius(), Err(Error::InvalidFrame { raw: 0xFFFF }));
+    }
+}
@@ -0,0 +1,263 @@
+//! # converter
+//!
+//! The MAX6675's siblings, behind one trait.
+//!
+//! Maxim makes a whole family of thermocouple-to-digital converters, and they
+//! all work about the same: clock a frame out over SPI, then pull a
+//! temperature and some fault bits out of it. [`ThermocoupleConverter`] is
+//! what they have in common, so the rest of your code doesn't need to care
+//! which one is on the board.
+//!
+//! - [`Max6675`]: 12 bits, 0 to 1023.75° C, type K.
+//! - [`max6674::Max6674`]: 10 bits, 0 to 127.875° C, type K.
+//! - [`max31855::Max31855`]: 14 bits, -270 to 1800° C, with the chip's own
+//!   temperature and short-circuit faults.
+//! - [`max31856::Max31856`]: 19 bits, register-based, and works with any
+//!   thermocouple type.
+//!
+//! Open thermocouples are always [`Error::OpenCircuit`]. Other faults are
+//! [`Error::Fault`].
+//!
+//! ## Example
+//!
+//! ```no_run
+//!
+//! use linux_max6675::{converter::max31855::Max31855, Max6675, ThermocoupleConverter};
+//!
+//! let mut converters: Vec<Box<dyn ThermocoupleConverter>> = vec![
+//!     Box::new(Max6675::new("/dev/spidev0.0").unwrap()),
+//!     Box::new(Max31855::new("/dev/spidev0.1").unwrap()),
+//! ];
+//!
+//! for converter in &mut converters {
+//!     let conversion = converter.read_conversion().unwrap();
+//!     println!("{}: {}° C", converter.part(), conversion.thermocouple);
+//! }
+//!
+//! ```
+
+use std::{
+    fmt,
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
+
+use embedded_hal::spi::SpiDevice;
+
+use crate::{Error, Max6675, Sensor};
+
+pub mod max31855;
+pub mod max31856;
+pub mod max6674;
+
+/// One conversion from a thermocouple converter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Conversion {
+    /// The thermocouple's temperature, in ° C.
+    pub thermocouple: f64,
+    /// The chip's own temperature (the cold junction), in ° C, for chips that
+    /// report it.
+    pub cold_junction: Option<f64>,
+}
+
+/// Something wrong with the thermocouple, other than it being open.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Fault {
+    /// The thermocouple is shorted to ground.
+    ShortToGround,
+    /// The thermocouple is shorted to VCC.
+    ShortToVcc,
+    /// The chip's own temperature is outside the range it can work in.
+    ColdJunctionRange,
+    /// The thermocouple's temperature is outside its type's range.
+    ThermocoupleRange,
+    /// The chip's own temperature is above its high threshold.
+    ColdJunctionHigh,
+    /// The chip's own temperature is below its low threshold.
+    ColdJunctionLow,
+    /// The thermocouple's temperature is above its high threshold.
+    ThermocoupleHigh,
+    /// The thermocouple's temperature is below its low threshold.
+    ThermocoupleLow,
+    /// The thermocouple input is over or under voltage.
+    OverUnderVoltage,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Fault::ShortToGround => "the thermocouple is shorted to ground",
+            Fault::ShortToVcc => "the thermocouple is shorted to VCC",
+            Fault::ColdJunctionRange => "the chip's temperature is out of range",
+            Fault::ThermocoupleRange => "the thermocouple's temperature is out of range",
+            Fault::ColdJunctionHigh => "the chip's temperature is too high",
+            Fault::ColdJunctionLow => "the chip's temperature is too low",
+            Fault::ThermocoupleHigh => "the thermocouple's temperature is too high",
+            Fault::ThermocoupleLow => "the thermocouple's temperature is too low",
+            Fault::OverUnderVoltage => "the thermocouple input is over or under voltage",
+        })
+    }
+}
+
+/// A Maxim thermocouple-to-digital converter, like the MAX6675.
+///
+/// Every converter is a [`Sensor`] too, so filters, validators and the like
+/// work on all of them.
+pub trait ThermocoupleConverter: Sensor {
+    /// The chip's part number, like `"MAX6675"`.
+    fn part(&self) -> &'static str;
+
+    /// The temperatures the chip can report, in ° C.
+    fn range(&self) -> RangeInclusive<f64>;
+
+    /// The smallest change in temperature the chip can see, in ° C.
+    fn resolution(&self) -> f64;
+
+    /// Tries to read a conversion.
+    ///
+    /// Fails with [`Error::OpenCircuit`] or [`Error::Fault`] if the chip
+    /// reports a problem with the thermocouple.
+    fn read_conversion(&mut self) -> Result<Conversion, Error>;
+}
+
+impl<C: ThermocoupleConverter + ?Sized> ThermocoupleConverter for &mut C {
+    fn part(&self) -> &'static str {
+        (**self).part()
+    }
+
+    fn range(&self) -> RangeInclusive<f64> {
+        (**self).range()
+    }
+
+    fn resolution(&self) -> f64 {
+        (**self).resolution()
+    }
+
+    fn read_conversion(&mut self) -> Result<Conversion, Error> {
+        (**self).read_conversion()
+    }
+}
+
+impl<C: ThermocoupleConverter + ?Sized> ThermocoupleConverter for Box<C> {
+    fn part(&self) -> &'static str {
+        (**self).part()
+    }
+
+    fn range(&self) -> RangeInclusive<f64> {
+        (**self).range()
+    }
+
+    fn resolution(&self) -> f64 {
+        (**self).resolution()
+    }
+
+    fn read_conversion(&mut self) -> Result<Conversion, Error> {
+        (**self).read_conversion()
+    }
+}
+
+impl<SPI> ThermocoupleConverter for Max6675<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    fn part(&self) -> &'static str {
+        "MAX6675"
+    }
+
+    fn range(&self) -> RangeInclusive<f64> {
+        0.0..=1023.75
+    }
+
+    fn resolution(&self) -> f64 {
+        0.25
+    }
+
+    /// Reads the corrected temperature, just like [`Max6675::read_celsius`].
+    /// The MAX6675 doesn't report its own temperature.
+    fn read_conversion(&mut self) -> Result<Conversion, Error> {
+        Ok(Conversion {
+            thermocouple: Max6675::read_celsius(self)?,
+            cold_junction: None,
+        })
+    }
+}
+
+/// Keeps track of a chip's conversion time, for the chips that start a new
+/// conversion every time you read them.
+#[derive(Clone, Copy, Debug)]
+struct Pacer {
+    conversion_time: Duration,
+    /// When the last conversion started.
+    last_read: Option<Instant>,
+}
+
+impl Pacer {
+    fn new(conversion_time: Duration) -> Self {
+        Self {
+            conversion_time,
+            last_read: None,
+        }
+    }
+
+    /// Sleeps until the last conversion is done.
+    fn wait(&self) {
+        if let Some(last) = self.last_read {
+            std::thread::sleep(self.conversion_time.saturating_sub(last.elapsed()));
+        }
+    }
+
+    /// Notes that a new conversion just started.
+    fn started(&mut self) {
+        self.last_read = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{max31855::Max31855, max6674::Max6674, *};
+    use crate::backend::mock::MockMax6675;
+
+    #[test]
+    fn drives_them_all_the_same() {
+        let mut max6675 = MockMax6675::new();
+        max6675.push_celsius(20.0);
+        let mut max6675 = Max6675::from_spi(max6675);
+        max6675.set_conversion_time(Duration::ZERO);
+
+        let mut max6674 = MockMax6675::new();
+        max6674.push_frame(160 << 5);
+        let mut max6674 = Max6674::from_spi(max6674);
+        max6674.set_conversion_time(Duration::ZERO);
+
+        let mut max31855 = MockMax6675::new();
+        max31855.push_bytes(((80_u32 << 18) | (400 << 4)).to_be_bytes());
+        let mut max31855 = Max31855::from_spi(max31855);
+        max31855.set_conversion_time(Duration::ZERO);
+
+        let mut converters: Vec<Box<dyn ThermocoupleConverter>> =
+            vec![Box::new(max6675), Box::new(max6674), Box::new(max31855)];
+
+        let parts: Vec<_> = converters.iter().map(|c| c.part()).collect();
+        assert_eq!(parts, ["MAX6675", "MAX6674", "MAX31855"]);
+
+        for converter in &mut converters {
+            assert_eq!(converter.read_conversion().unwrap().thermocouple, 20.0);
+            // and they're all sensors, too
+            assert_eq!(converter.read_celsius(), Err(Error::ReceivedNothing));
+        }
+    }
+
+    #[test]
+    fn faults_read_nicely() {
+        let e = Error::Fault {
+            fault: Fault::ShortToGround,
+        };
+        assert!(e.to_string().contains("shorted to ground"), "{e}");
+    }
+}
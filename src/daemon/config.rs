@@ -0,0 +1,271 @@
+//! The daemon's TOML configuration.
+
+use std::{path::PathBuf, time::Duration};
+
+use serde::Deserialize;
+
+/// Everything the daemon needs to know.
+///
+/// ## Example
+///
+/// ```toml
+/// [[sensor]]
+/// name = "furnace"
+/// device = "/dev/spidev0.0"
+/// interval = 1.0
+/// unit = "celsius"
+/// linearization = "nist-type-k"
+/// calibration = "/etc/max6675d/furnace.toml"
+///
+/// [[sink]]
+/// type = "stdout"
+///
+/// [[sink]]
+/// type = "csv"
+/// path = "/var/log/max6675d/readings.csv"
+/// max_bytes = 10_000_000
+/// keep = 5
+///
+/// [[sink]]
+/// type = "json"
+/// path = "/var/log/max6675d/readings.jsonl"
+///
+/// [[sink]]
+/// type = "unix"
+/// path = "/run/max6675d.sock"
+///
+/// # needs the `metrics` feature
+/// [[sink]]
+/// type = "prometheus"
+/// listen = "0.0.0.0:9675"
+///
+/// # needs the `mqtt` feature
+/// [[sink]]
+/// type = "mqtt"
+/// broker = "localhost:1883"
+/// client_id = "shop"
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The sensors to poll.
+    #[serde(rename = "sensor", default)]
+    pub sensors: Vec<SensorConfig>,
+    /// Where readings go.
+    #[serde(rename = "sink", default)]
+    pub sinks: Vec<SinkConfig>,
+}
+
+impl std::str::FromStr for Config {
+    type Err = toml::de::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s)
+    }
+}
+
+/// One sensor to poll.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SensorConfig {
+    /// What to call this sensor in the output.
+    pub name: String,
+    /// The spidev the sensor is on, like `/dev/spidev0.0`.
+    pub device: PathBuf,
+    /// Seconds between reads. The chip can't go faster than about 0.22.
+    ///
+    /// This has to be finite and positive, or the daemon won't start.
+    #[serde(default = "SensorConfig::default_interval")]
+    pub interval: f64,
+    /// What unit to print readings in on stdout. Everything else gets a
+    /// [`Record`](crate::format::Record), which is always in Celsius.
+    #[serde(default)]
+    pub unit: Unit,
+    /// How to correct for the thermocouple's non-linearity.
+    #[serde(default)]
+    pub linearization: crate::Linearization,
+    /// A calibration file for this sensor's probe, in TOML or JSON.
+    pub calibration: Option<PathBuf>,
+}
+
+impl SensorConfig {
+    fn default_interval() -> f64 {
+        1.0
+    }
+
+    /// The interval as a [`Duration`].
+    ///
+    /// Intervals that are too big for a `Duration` (like `inf`) come out as
+    /// [`Duration::MAX`], and ones that aren't positive (like `nan`) as zero.
+    pub fn interval(&self) -> Duration {
+        if self.interval > 0.0 {
+            Duration::try_from_secs_f64(self.interval).unwrap_or(Duration::MAX)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+/// A temperature unit.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+impl Unit {
+    /// The unit's name, as it appears in the config.
+    pub fn name(self) -> &'static str {
+        match self {
+            Unit::Celsius => "celsius",
+            Unit::Fahrenheit => "fahrenheit",
+            Unit::Kelvin => "kelvin",
+        }
+    }
+
+    /// Converts a temperature in Celsius into this unit.
+    pub fn convert(self, celsius: f64) -> f64 {
+        match self {
+            Unit::Celsius => celsius,
+            Unit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+            Unit::Kelvin => celsius + 273.15,
+        }
+    }
+}
+
+/// Where readings go.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum SinkConfig {
+    /// Human-readable lines on stdout.
+    Stdout,
+    /// CSV rows in a file that rotates once it gets too big.
+    Csv {
+        path: PathBuf,
+        /// Rotate once the file reaches this many bytes.
+        #[serde(default = "SinkConfig::default_max_bytes")]
+        max_bytes: u64,
+        /// How many old files to keep around.
+        #[serde(default = "SinkConfig::default_keep")]
+        keep: usize,
+    },
+    /// JSON lines, appended to a file.
+    Json { path: PathBuf },
+    /// JSON lines, sent to everyone connected to a UNIX socket.
+    Unix { path: PathBuf },
+    /// A Prometheus exporter, serving `/metrics` on `listen`.
+    #[cfg(feature = "metrics")]
+    Prometheus { listen: String },
+    /// An MQTT broker, like `localhost:1883`.
+    #[cfg(feature = "mqtt")]
+    Mqtt {
+        broker: String,
+        #[serde(default = "SinkConfig::default_client_id")]
+        client_id: String,
+        /// Defaults to `max6675/<client_id>`.
+        topic_prefix: Option<String>,
+        /// Whether to send Home Assistant discovery configs.
+        #[serde(default = "SinkConfig::default_discovery")]
+        discovery: bool,
+    },
+}
+
+impl SinkConfig {
+    fn default_max_bytes() -> u64 {
+        10 * 1024 * 1024
+    }
+
+    fn default_keep() -> usize {
+        5
+    }
+
+    #[cfg(feature = "mqtt")]
+    fn default_client_id() -> String {
+        "max6675d".into()
+    }
+
+    #[cfg(feature = "mqtt")]
+    fn default_discovery() -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_full_config() {
+        let config: Config = r#"
+            [[sensor]]
+            name = "furnace"
+            device = "/dev/spidev0.0"
+            interval = 0.5
+            unit = "fahrenheit"
+            linearization = "nist-type-k"
+            calibration = "furnace.toml"
+
+            [[sensor]]
+            name = "exhaust"
+            device = "/dev/spidev0.1"
+
+            [[sink]]
+            type = "stdout"
+
+            [[sink]]
+            type = "csv"
+            path = "readings.csv"
+            keep = 2
+
+            [[sink]]
+            type = "unix"
+            path = "/run/max6675d.sock"
+        "#
+        .parse()
+        .unwrap();
+
+        assert_eq!(config.sensors.len(), 2);
+        assert_eq!(config.sensors[0].interval(), Duration::from_millis(500));
+        assert_eq!(config.sensors[0].unit, Unit::Fahrenheit);
+        assert_eq!(
+            config.sensors[0].linearization,
+            crate::Linearization::NistTypeK
+        );
+        assert_eq!(
+            config.sensors[0].calibration,
+            Some(PathBuf::from("furnace.toml"))
+        );
+        assert_eq!(config.sensors[1].interval(), Duration::from_secs(1));
+        assert_eq!(config.sensors[1].unit, Unit::Celsius);
+
+        assert_eq!(
+            config.sinks,
+            vec![
+                SinkConfig::Stdout,
+                SinkConfig::Csv {
+                    path: "readings.csv".into(),
+                    max_bytes: 10 * 1024 * 1024,
+                    keep: 2
+                },
+                SinkConfig::Unix {
+                    path: "/run/max6675d.sock".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_typos() {
+        assert!(
+            "[[sensor]]\nname = \"a\"\ndevice = \"/dev/spidev0.0\"\nintervl = 1.0"
+                .parse::<Config>()
+                .is_err()
+        );
+        assert!("[[sink]]\ntype = \"carrier-pigeon\""
+            .parse::<Config>()
+            .is_err());
+    }
+}
@@ -0,0 +1,467 @@
+//! # daemon
+//!
+//! The pieces behind `max6675d`, a long-running logger for MAX6675s.
+//!
+//! The daemon reads a TOML [`Config`] listing sensors and sinks, polls each
+//! sensor on its own interval (the driver makes sure the chip's conversion
+//! time is respected), and sends every reading to every [`Sink`].
+
+use std::{
+    io,
+    path::Path,
+    time::{Duration, Instant, SystemTime},
+};
+
+use embedded_hal::spi::SpiDevice;
+use thiserror::Error;
+
+use crate::{
+    calibration::CalibrationError,
+    format::{Faults, Record},
+    Calibration, Max6675, Spidev,
+};
+
+mod config;
+pub mod sink;
+
+pub use config::{Config, SensorConfig, SinkConfig, Unit};
+pub use sink::Sink;
+
+/// The longest the daemon sleeps without giving the sinks a chance to
+/// [`Sink::idle`].
+const IDLE_TICK: Duration = Duration::from_secs(1);
+
+/// An error that stops the daemon from starting up.
+#[derive(Debug, Error)]
+pub enum DaemonError {
+    #[error("Couldn't read the config file: {0}")]
+    ReadConfig(#[source] io::Error),
+    #[error("The config file is invalid: {0}")]
+    ParseConfig(#[from] toml::de::Error),
+    #[error("Couldn't open sensor `{name}`: {source}")]
+    Sensor {
+        name: String,
+        #[source]
+        source: crate::Error,
+    },
+    #[error("Sensor `{name}` has an interval of {interval} seconds, but it needs to be a finite, positive number.")]
+    Interval { name: String, interval: f64 },
+    #[error("Couldn't load the calibration for sensor `{name}`: {source}")]
+    Calibration {
+        name: String,
+        #[source]
+        source: CalibrationError,
+    },
+    #[error("Couldn't open a sink: {0}")]
+    Sink(#[source] io::Error),
+}
+
+/// One reading from one sensor, ready to be sent somewhere.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    /// The sensor's name from the config.
+    pub sensor: String,
+    /// When the reading was taken.
+    pub timestamp: SystemTime,
+    /// The unit the sensor's readings are printed in on stdout.
+    pub unit: Unit,
+    /// The raw word from the chip, if we got that far.
+    pub raw: Option<u16>,
+    /// The temperature in ° C, or what went wrong.
+    pub celsius: Result<f64, crate::Error>,
+}
+
+impl Sample {
+    /// Turns the sample into a [`Record`], the same schema the rest of the
+    /// crate uses.
+    pub fn to_record(&self) -> Record {
+        Record {
+            timestamp: self.timestamp,
+            sensor: self.sensor.clone(),
+            raw: self.raw,
+            celsius: self.celsius.as_ref().ok().copied(),
+            faults: self.raw.map(Faults::from_raw).unwrap_or_default(),
+            error: self.celsius.as_ref().err().cloned(),
+        }
+    }
+}
+
+/// What happened in one [`Daemon::step`].
+#[derive(Debug)]
+pub struct Step {
+    /// The sample that was sent to the sinks.
+    pub sample: Sample,
+    /// The sinks that couldn't take it, by the order they were added in, and
+    /// why.
+    pub sink_errors: Vec<(usize, io::Error)>,
+}
+
+/// A sensor, along with when it's due to be read next.
+#[derive(Debug)]
+struct Scheduled<SPI> {
+    config: SensorConfig,
+    device: Max6675<SPI>,
+    due: Instant,
+}
+
+/// Polls sensors and sends their readings to sinks.
+///
+/// ## Example
+///
+/// ```no_run
+///
+/// use linux_max6675::daemon::Daemon;
+///
+/// let mut daemon = Daemon::from_config_file("/etc/max6675d.toml").unwrap();
+/// daemon.run(|sink, e| eprintln!("couldn't write to sink {sink}: {e}"));
+///
+/// ```
+pub struct Daemon<SPI> {
+    sensors: Vec<Scheduled<SPI>>,
+    sinks: Vec<Box<dyn Sink>>,
+}
+
+impl Daemon<Spidev> {
+    /// Loads a config file, then opens everything in it.
+    pub fn from_config_file(path: impl AsRef<Path>) -> Result<Self, DaemonError> {
+        let config = std::fs::read_to_string(path).map_err(DaemonError::ReadConfig)?;
+        Self::from_config(config.parse()?)
+    }
+
+    /// Opens every sensor and sink in the config.
+    pub fn from_config(config: Config) -> Result<Self, DaemonError> {
+        let mut daemon = Self::new();
+
+        for sensor in config.sensors {
+            if !(sensor.interval.is_finite() && sensor.interval > 0.0) {
+                return Err(DaemonError::Interval {
+                    name: sensor.name,
+                    interval: sensor.interval,
+                });
+            }
+
+            let mut device =
+                Max6675::new(&sensor.device).map_err(|source| DaemonError::Sensor {
+                    name: sensor.name.clone(),
+                    source,
+                })?;
+
+            device.set_linearization(sensor.linearization);
+            if let Some(path) = &sensor.calibration {
+                let calibration =
+                    Calibration::load(path).map_err(|source| DaemonError::Calibration {
+                        name: sensor.name.clone(),
+                        source,
+                    })?;
+                device.set_calibration(Some(calibration));
+            }
+
+            daemon.add_sensor(sensor, device);
+        }
+
+        for sink in config.sinks {
+            daemon.add_sink(open_sink(sink).map_err(DaemonError::Sink)?);
+        }
+
+        Ok(daemon)
+    }
+}
+
+/// Opens the sink described by the config.
+fn open_sink(config: SinkConfig) -> io::Result<Box<dyn Sink>> {
+    Ok(match config {
+        SinkConfig::Stdout => Box::new(sink::StdoutSink),
+        SinkConfig::Csv {
+            path,
+            max_bytes,
+            keep,
+        } => Box::new(sink::CsvSink::open(path, max_bytes, keep)?),
+        SinkConfig::Json { path } => Box::new(sink::JsonSink::open(path)?),
+        SinkConfig::Unix { path } => Box::new(sink::UnixSink::bind(path)?),
+        #[cfg(feature = "metrics")]
+        SinkConfig::Prometheus { listen } => Box::new(sink::PrometheusSink::bind(listen)?),
+        #[cfg(feature = "mqtt")]
+        SinkConfig::Mqtt {
+            broker,
+            client_id,
+            topic_prefix,
+            discovery,
+        } => {
+            let mut options = crate::mqtt::MqttOptions::new(client_id);
+            if let Some(topic_prefix) = topic_prefix {
+                options.topic_prefix = topic_prefix;
+            }
+            if !discovery {
+                options.discovery_prefix = None;
+            }
+            Box::new(sink::MqttSink::connect(broker, options)?)
+        }
+    })
+}
+
+impl<SPI> Default for Daemon<SPI> {
+    fn default() -> Self {
+        Self {
+            sensors: Vec::new(),
+            sinks: Vec::new(),
+        }
+    }
+}
+
+impl<SPI> Daemon<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    /// Creates a daemon with no sensors or sinks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a sensor, which is due to be read right away.
+    pub fn add_sensor(&mut self, config: SensorConfig, device: Max6675<SPI>) {
+        self.sensors.push(Scheduled {
+            config,
+            device,
+            due: Instant::now(),
+        });
+    }
+
+    /// Adds somewhere for readings to go.
+    pub fn add_sink(&mut self, sink: Box<dyn Sink>) {
+        self.sinks.push(sink);
+    }
+
+    /// Waits for the next sensor that's due, reads it, and sends the sample
+    /// to every sink.
+    ///
+    /// Returns the sample, along with any sinks that failed to take it (or
+    /// failed while we were waiting), or `None` if there aren't any sensors.
+    /// A sink failing doesn't stop the others from getting the sample.
+    pub fn step(&mut self) -> Option<Step> {
+        let sensor = self.sensors.iter_mut().min_by_key(|s| s.due)?;
+        let mut sink_errors = Vec::new();
+
+        // wake up now and then while we wait, so sinks can keep their
+        // connections alive
+        loop {
+            let now = Instant::now();
+            if sensor.due <= now {
+                break;
+            }
+
+            std::thread::sleep((sensor.due - now).min(IDLE_TICK));
+            for (n, sink) in self.sinks.iter_mut().enumerate() {
+                if let Err(e) = sink.idle() {
+                    sink_errors.push((n, e));
+                }
+            }
+        }
+
+        let (raw, celsius) = match sensor.device.read_frame() {
+            Ok(reading) if reading.is_open() => {
+                (Some(reading.raw()), Err(crate::Error::OpenCircuit))
+            }
+            Ok(reading) => (
+                Some(reading.raw()),
+                Ok(sensor.device.corrected_celsius(reading.temperature())),
+            ),
+            Err(e) => (None, Err(e)),
+        };
+
+        let sample = Sample {
+            sensor: sensor.config.name.clone(),
+            timestamp: SystemTime::now(),
+            unit: sensor.config.unit,
+            raw,
+            celsius,
+        };
+
+        // if we've fallen behind, don't try to catch up all at once
+        sensor.due += sensor.config.interval();
+        let now = Instant::now();
+        if sensor.due < now {
+            sensor.due = now;
+        }
+
+        sink_errors.extend(
+            self.sinks
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(n, sink)| sink.write(&sample).err().map(|e| (n, e))),
+        );
+
+        Some(Step {
+            sample,
+            sink_errors,
+        })
+    }
+
+    /// Polls forever, calling `on_sink_error` with the sink's number and the
+    /// error whenever a sink fails.
+    ///
+    /// Only returns if there are no sensors to poll.
+    pub fn run(&mut self, mut on_sink_error: impl FnMut(usize, io::Error)) {
+        while let Some(step) = self.step() {
+            for (sink, e) in step.sink_errors {
+                on_sink_error(sink, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{backend::mock::MockMax6675, Error};
+
+    /// A sink that just keeps everything.
+    #[derive(Clone, Default)]
+    struct Memory(Arc<Mutex<Vec<Sample>>>);
+
+    impl Sink for Memory {
+        fn write(&mut self, sample: &Sample) -> io::Result<()> {
+            self.0.lock().unwrap().push(sample.clone());
+            Ok(())
+        }
+    }
+
+    fn sensor(name: &str, interval: f64, unit: Unit) -> SensorConfig {
+        SensorConfig {
+            name: name.into(),
+            device: "/dev/null".into(),
+            interval,
+            unit,
+            linearization: Default::default(),
+            calibration: None,
+        }
+    }
+
+    #[test]
+    fn polls_sensors_on_their_own_schedules() {
+        let mut fast = MockMax6675::new();
+        fast.push_profile([10.0, 11.0, 12.0]);
+        let mut slow = MockMax6675::new();
+        slow.push_profile([100.0]).push_open_circuit();
+
+        let mut daemon = Daemon::new();
+        daemon.add_sensor(sensor("fast", 0.05, Unit::Celsius), Max6675::from_spi(fast));
+        daemon.add_sensor(sensor("slow", 0.12, Unit::Kelvin), Max6675::from_spi(slow));
+        for sensor in &mut daemon.sensors {
+            sensor.device.set_conversion_time(Duration::ZERO);
+        }
+
+        let memory = Memory::default();
+        daemon.add_sink(Box::new(memory.clone()));
+
+        let names: Vec<_> = (0..5)
+            .map(|_| daemon.step().unwrap().sample.sensor)
+            .collect();
+        assert_eq!(names, ["fast", "slow", "fast", "fast", "slow"]);
+
+        let samples = memory.0.lock().unwrap();
+        assert_eq!(samples[1].celsius, Ok(100.0));
+        assert_eq!(samples[1].unit, Unit::Kelvin);
+        assert_eq!(samples[3].celsius, Ok(12.0));
+        assert_eq!(samples[4].celsius, Err(Error::OpenCircuit));
+        assert_eq!(samples[4].raw, Some(0x0004));
+    }
+
+    #[test]
+    fn samples_stay_in_celsius() {
+        let mut mock = MockMax6675::new();
+        mock.push_celsius(0.25);
+        let mut daemon = Daemon::new();
+        daemon.add_sensor(sensor("a", 1.0, Unit::Fahrenheit), Max6675::from_spi(mock));
+
+        // the unit is only for stdout, so nothing gets converted there and
+        // back again
+        let sample = daemon.step().unwrap().sample;
+        assert_eq!(sample.celsius, Ok(0.25));
+        assert_eq!(sample.to_record().celsius, Some(0.25));
+    }
+
+    /// A sink that never works.
+    struct Broken;
+
+    impl Sink for Broken {
+        fn write(&mut self, _: &Sample) -> io::Result<()> {
+            Err(io::Error::other("broken"))
+        }
+
+        fn idle(&mut self) -> io::Result<()> {
+            Err(io::Error::other("still broken"))
+        }
+    }
+
+    #[test]
+    fn hands_back_sink_errors() {
+        let mut mock = MockMax6675::new();
+        mock.push_celsius(20.0);
+        let mut daemon = Daemon::new();
+        daemon.add_sensor(sensor("a", 1.0, Unit::Celsius), Max6675::from_spi(mock));
+
+        let memory = Memory::default();
+        daemon.add_sink(Box::new(Broken));
+        daemon.add_sink(Box::new(memory.clone()));
+
+        let step = daemon.step().unwrap();
+        assert_eq!(step.sink_errors.len(), 1);
+        assert_eq!(step.sink_errors[0].0, 0);
+        assert_eq!(step.sink_errors[0].1.to_string(), "broken");
+
+        // the other sinks still got it
+        assert_eq!(memory.0.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn sinks_idle_while_we_wait() {
+        let mut mock = MockMax6675::new();
+        mock.push_profile([20.0, 21.0]);
+        let mut max = Max6675::from_spi(mock);
+        max.set_conversion_time(Duration::ZERO);
+
+        let mut daemon = Daemon::new();
+        daemon.add_sensor(sensor("a", 0.05, Unit::Celsius), max);
+        daemon.add_sink(Box::new(Broken));
+
+        // the first reading's due right away, so there's no waiting
+        assert_eq!(daemon.step().unwrap().sink_errors.len(), 1);
+
+        let errors: Vec<_> = daemon
+            .step()
+            .unwrap()
+            .sink_errors
+            .into_iter()
+            .map(|(_, e)| e.to_string())
+            .collect();
+        assert_eq!(errors, ["still broken", "broken"]);
+    }
+
+    #[test]
+    fn rejects_bad_intervals() {
+        for interval in [f64::INFINITY, f64::NAN, 0.0, -1.0] {
+            let config = Config {
+                sensors: vec![sensor("furnace", interval, Unit::Celsius)],
+                sinks: Vec::new(),
+            };
+
+            assert!(matches!(
+                Daemon::from_config(config),
+                Err(DaemonError::Interval { name, .. }) if name == "furnace"
+            ));
+        }
+    }
+
+    #[test]
+    fn no_sensors_means_nothing_to_do() {
+        let mut daemon: Daemon<MockMax6675> = Daemon::new();
+        assert!(daemon.step().is_none());
+        daemon.run(|_, e| panic!("{e}"));
+    }
+}
@@ -0,0 +1,433 @@
+//! Places the daemon can send its readings.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+};
+
+use super::Sample;
+use crate::format::Record;
+
+/// Somewhere readings go.
+pub trait Sink {
+    /// Sends one sample along.
+    fn write(&mut self, sample: &Sample) -> io::Result<()>;
+
+    /// Called every so often while the daemon waits for the next reading,
+    /// for sinks with connections to keep alive. Does nothing by default.
+    fn idle(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Prints human-readable lines to stdout, in each sensor's own unit.
+///
+/// This is the only sink that cares about [`Sample::unit`]. Everything else
+/// sends Celsius.
+#[derive(Debug, Default)]
+pub struct StdoutSink;
+
+impl StdoutSink {
+    /// The line we print for a sample.
+    fn line(sample: &Sample) -> String {
+        match &sample.celsius {
+            Ok(celsius) => format!(
+                "{}: {} ({})",
+                sample.sensor,
+                sample.unit.convert(*celsius),
+                sample.unit.name()
+            ),
+            Err(e) => format!("{}: {e}", sample.sensor),
+        }
+    }
+}
+
+impl Sink for StdoutSink {
+    fn write(&mut self, sample: &Sample) -> io::Result<()> {
+        writeln!(io::stdout().lock(), "{}", Self::line(sample))
+    }
+}
+
+/// Writes CSV rows to a file, rotating it once it gets too big.
+///
+/// Rows are [`Record`]s, so they're always in Celsius.
+///
+/// Old files get a number tacked onto the end: `readings.csv.1` is the most
+/// recent, then `readings.csv.2`, and so on, up to `keep` of them.
+#[derive(Debug)]
+pub struct CsvSink {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    file: File,
+    written: u64,
+}
+
+impl CsvSink {
+    /// Opens (or creates) the CSV file at `path`.
+    pub fn open(path: impl Into<PathBuf>, max_bytes: u64, keep: usize) -> io::Result<Self> {
+        let path = path.into();
+        let (file, written) = Self::open_file(&path)?;
+
+        Ok(Self {
+            path,
+            max_bytes,
+            keep,
+            file,
+            written,
+        })
+    }
+
+    /// Opens the file for appending, writing a header if it's brand new.
+    fn open_file(path: &Path) -> io::Result<(File, u64)> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut written = file.metadata()?.len();
+
+        if written == 0 {
+            writeln!(file, "{}", Record::CSV_HEADER)?;
+            written = Record::CSV_HEADER.len() as u64 + 1;
+        }
+
+        Ok((file, written))
+    }
+
+    /// The path of the `n`th old file.
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+        path.into()
+    }
+
+    /// Shuffles the old files along and starts a fresh one.
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            // `.keep` falls off the end, then everything else moves up one
+            for n in (1..self.keep).rev() {
+                let from = self.rotated(n);
+                if from.exists() {
+                    fs::rename(from, self.rotated(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+
+        (self.file, self.written) = Self::open_file(&self.path)?;
+        Ok(())
+    }
+}
+
+impl Sink for CsvSink {
+    fn write(&mut self, sample: &Sample) -> io::Result<()> {
+        let line = sample.to_record().to_csv();
+
+        if self.written + line.len() as u64 + 1 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{line}")?;
+        self.written += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+/// Appends [`Record`]s to a file as JSON lines, always in Celsius.
+#[derive(Debug)]
+pub struct JsonSink {
+    file: File,
+}
+
+impl JsonSink {
+    /// Opens (or creates) the file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+}
+
+impl Sink for JsonSink {
+    fn write(&mut self, sample: &Sample) -> io::Result<()> {
+        writeln!(self.file, "{}", sample.to_record().to_json())
+    }
+}
+
+/// Sends [`Record`]s as JSON lines to everyone connected to a UNIX socket.
+///
+/// Clients can come and go whenever they like. Try it out with something like
+/// `socat - UNIX-CONNECT:/run/max6675d.sock`! Clients that fall behind (and
+/// fill up the socket's buffer) get dropped, so they can't hold up the daemon.
+#[derive(Debug)]
+pub struct UnixSink {
+    path: PathBuf,
+    listener: UnixListener,
+    clients: Vec<UnixStream>,
+}
+
+impl UnixSink {
+    /// Listens on a socket at `path`, replacing any old one that's there.
+    pub fn bind(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => (),
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            path,
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    /// Picks up anyone who connected since last time.
+    fn accept(&mut self) -> io::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((client, _)) => {
+                    // so a client that stops reading can't stall us
+                    client.set_nonblocking(true)?;
+                    self.clients.push(client);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Sink for UnixSink {
+    fn write(&mut self, sample: &Sample) -> io::Result<()> {
+        self.accept()?;
+
+        let line = format!("{}\n", sample.to_record().to_json());
+        // anyone who hung up, or isn't keeping up (`WouldBlock`), gets dropped
+        self.clients
+            .retain_mut(|client| client.write_all(line.as_bytes()).is_ok());
+
+        Ok(())
+    }
+}
+
+impl Drop for UnixSink {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Serves readings to Prometheus over HTTP.
+///
+/// Temperatures are exported in Celsius, whatever the sensor's unit.
+#[cfg(feature = "metrics")]
+#[derive(Debug)]
+pub struct PrometheusSink {
+    metrics: crate::metrics::Metrics,
+    exporter: crate::metrics::Exporter,
+}
+
+#[cfg(feature = "metrics")]
+impl PrometheusSink {
+    /// Starts an exporter on `addr`, like `"0.0.0.0:9675"`.
+    pub fn bind(addr: impl std::net::ToSocketAddrs) -> io::Result<Self> {
+        let metrics = crate::metrics::Metrics::new();
+        let exporter = crate::metrics::Exporter::bind(addr, metrics.clone())?;
+        Ok(Self { metrics, exporter })
+    }
+
+    /// The address the exporter is listening on.
+    pub fn local_addr(&self) -> std::net::SocketAddr {
+        self.exporter.local_addr()
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Sink for PrometheusSink {
+    fn write(&mut self, sample: &Sample) -> io::Result<()> {
+        self.metrics.record(&sample.sensor, &sample.celsius);
+        Ok(())
+    }
+}
+
+/// Publishes readings to an MQTT broker.
+///
+/// Like [`PrometheusSink`], this always sends Celsius. It pings the broker
+/// while the daemon's idle, and connects again if the broker goes away.
+#[cfg(feature = "mqtt")]
+#[derive(Debug)]
+pub struct MqttSink {
+    publisher: crate::mqtt::Publisher,
+}
+
+#[cfg(feature = "mqtt")]
+impl MqttSink {
+    /// Connects to the broker at `addr`.
+    pub fn connect(
+        addr: impl std::net::ToSocketAddrs,
+        options: crate::mqtt::MqttOptions,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            publisher: crate::mqtt::Publisher::connect(addr, options)?,
+        })
+    }
+}
+
+#[cfg(feature = "mqtt")]
+impl Sink for MqttSink {
+    fn write(&mut self, sample: &Sample) -> io::Result<()> {
+        self.publisher
+            .publish_celsius(&sample.sensor, &sample.celsius)
+    }
+
+    fn idle(&mut self) -> io::Result<()> {
+        self.publisher.ping_if_idle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+    use crate::{daemon::Unit, Error};
+
+    fn sample(celsius: Result<f64, Error>) -> Sample {
+        Sample {
+            sensor: "kiln, top".into(),
+            timestamp: UNIX_EPOCH + std::time::Duration::from_millis(1_700_000_000_250),
+            unit: Unit::Celsius,
+            raw: Some(0x0320),
+            celsius,
+        }
+    }
+
+    /// A quarter of a degree, for a sensor that wants Fahrenheit. Going to
+    /// Fahrenheit and back doesn't land on 0.25 again.
+    fn quarter_degree() -> Sample {
+        let mut sample = sample(Ok(0.25));
+        sample.unit = Unit::Fahrenheit;
+        sample
+    }
+
+    #[test]
+    fn only_stdout_uses_the_sensors_unit() {
+        assert_eq!(
+            StdoutSink::line(&quarter_degree()),
+            "kiln, top: 32.45 (fahrenheit)"
+        );
+        assert_eq!(
+            StdoutSink::line(&sample(Err(Error::OpenCircuit))),
+            format!("kiln, top: {}", Error::OpenCircuit)
+        );
+    }
+
+    #[test]
+    fn writes_records_in_celsius() {
+        let record = quarter_degree().to_record();
+        assert_eq!(record.celsius, Some(0.25));
+        assert_eq!(
+            record.to_csv(),
+            "1700000000.250,\"kiln, top\",800,0.25,false,false,false,"
+        );
+
+        let open = sample(Err(Error::OpenCircuit)).to_record();
+        assert_eq!(open.celsius, None);
+        assert_eq!(open.raw, Some(0x0320));
+        assert_eq!(open.error, Some(Error::OpenCircuit));
+    }
+
+    #[test]
+    fn csv_rotates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("readings.csv");
+        let mut sink = CsvSink::open(&path, 200, 2).unwrap();
+
+        for _ in 0..20 {
+            sink.write(&sample(Ok(21.0))).unwrap();
+        }
+
+        for file in [path.clone(), sink.rotated(1), sink.rotated(2)] {
+            let contents = fs::read_to_string(&file).unwrap();
+            assert!(contents.len() <= 200, "{file:?} is too big");
+            assert!(contents.starts_with(Record::CSV_HEADER));
+        }
+        assert!(!sink.rotated(3).exists());
+    }
+
+    #[test]
+    fn unix_socket_broadcasts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("max6675d.sock");
+        let mut sink = UnixSink::bind(&path).unwrap();
+
+        let client = UnixStream::connect(&path).unwrap();
+        sink.write(&sample(Ok(42.0))).unwrap();
+
+        let mut line = String::new();
+        io::BufReader::new(client).read_line(&mut line).unwrap();
+        assert!(line.contains(r#""celsius":42.0"#));
+
+        drop(sink);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn unix_socket_drops_slow_clients() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("max6675d.sock");
+        let mut sink = UnixSink::bind(&path).unwrap();
+
+        // connects, but never reads
+        let _client = UnixStream::connect(&path).unwrap();
+        for _ in 0..1_000_000 {
+            sink.write(&sample(Ok(42.0))).unwrap();
+            if sink.clients.is_empty() {
+                return;
+            }
+        }
+        panic!("the slow client never got dropped");
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn prometheus_exports_celsius() {
+        use std::{io::Read, net::TcpStream};
+
+        let mut sink = PrometheusSink::bind("127.0.0.1:0").unwrap();
+        sink.write(&quarter_degree()).unwrap();
+
+        let mut stream = TcpStream::connect(sink.local_addr()).unwrap();
+        write!(stream, "GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.contains("max6675_temperature_celsius{sensor=\"kiln, top\"} 0.25\n"));
+    }
+
+    #[cfg(feature = "mqtt")]
+    #[test]
+    fn mqtt_publishes_celsius() {
+        use crate::mqtt::{tests::Heard, MqttOptions};
+
+        let (addr, broker) = crate::mqtt::tests::broker(0);
+        let mut options = MqttOptions::new("max6675d");
+        options.discovery_prefix = None;
+
+        let mut sink = MqttSink::connect(addr, options).unwrap();
+        sink.write(&quarter_degree()).unwrap();
+        drop(sink);
+
+        assert!(broker.join().unwrap().contains(&Heard::Publish {
+            topic: "max6675/max6675d/kiln__top/temperature".into(),
+            payload: "0.25".into(),
+            retain: false,
+        }));
+    }
+}
@@ -0,0 +1,315 @@
+//! # discover
+//!
+//! Finds MAX6675s without guessing paths.
+//!
+//! [`Discovery`] looks through `/dev/spidev*` (and, with the `iio` feature,
+//! `/sys/bus/iio/devices/*` for chips bound to the kernel's thermocouple
+//! drivers), probes each one, and tells you what it found and where.
+//!
+//! ## Example
+//!
+//! ```no_run
+//!
+//! use linux_max6675::discover::Discovery;
+//!
+//! for found in Discovery::new().find().unwrap() {
+//!     println!(
+//!         "there's a MAX6675 on bus {:?}, chip select {:?}: {}",
+//!         found.bus,
+//!         found.chip_select,
+//!         found.path.display(),
+//!     );
+//! }
+//!
+//! ```
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{backend::io_error, Error, Max6675, Reading, Spidev};
+
+/// How a discovered device is connected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Interface {
+    /// Through spidev, like `/dev/spidev0.0`. Open it with [`Max6675::new`].
+    Spidev,
+    /// Through the kernel's IIO driver. Open it with
+    /// [`Iio::open`](crate::backend::iio::Iio::open).
+    #[cfg(feature = "iio")]
+    Iio,
+}
+
+/// Something [`Discovery`] found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Descriptor {
+    pub interface: Interface,
+    /// The spidev, or the IIO device's sysfs directory.
+    pub path: PathBuf,
+    /// The SPI bus number, if we could work it out.
+    pub bus: Option<u32>,
+    /// The chip select on that bus, if we could work it out.
+    pub chip_select: Option<u32>,
+    /// What the kernel calls the chip, for IIO devices (like `max6675`).
+    pub driver: Option<String>,
+    /// What we got when we probed it.
+    pub probe: Result<Reading, Error>,
+}
+
+impl Descriptor {
+    /// Whether there's a chip there, even if its thermocouple isn't plugged
+    /// in.
+    pub fn is_present(&self) -> bool {
+        matches!(self.probe, Ok(_) | Err(Error::OpenCircuit))
+    }
+}
+
+/// Probes a spidev for a MAX6675.
+pub type Prober = fn(&Path) -> Result<Reading, Error>;
+
+/// Looks for MAX6675s.
+///
+/// By default, it looks in `/dev` and `/sys`, and probes spidevs with
+/// [`Max6675::probe`], which takes most of a second for each one.
+///
+/// Probing has to switch each spidev to the MAX6675's SPI mode and clock
+/// speed, but puts them back afterwards, so whatever else is on the bus
+/// (flash, displays and so on) keeps working. It does still clock a few
+/// reads out of each device, which is harmless for most chips, but use
+/// [`Discovery::with_prober`] to be pickier.
+#[derive(Clone, Debug)]
+pub struct Discovery {
+    dev_root: PathBuf,
+    sys_root: PathBuf,
+    prober: Prober,
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Discovery {
+    /// Looks in the usual places.
+    pub fn new() -> Self {
+        Self {
+            dev_root: PathBuf::from("/dev"),
+            sys_root: PathBuf::from("/sys"),
+            // leave each spidev's settings the way we found them
+            prober: |path| Max6675::from_spi(Spidev::open_and_restore(path)?).probe(),
+        }
+    }
+
+    /// Looks for spidevs in `dev_root` instead of `/dev`.
+    pub fn with_dev_root(mut self, dev_root: impl Into<PathBuf>) -> Self {
+        self.dev_root = dev_root.into();
+        self
+    }
+
+    /// Looks for sysfs at `sys_root` instead of `/sys`.
+    pub fn with_sys_root(mut self, sys_root: impl Into<PathBuf>) -> Self {
+        self.sys_root = sys_root.into();
+        self
+    }
+
+    /// Probes spidevs with `prober` instead of opening them for real.
+    pub fn with_prober(mut self, prober: Prober) -> Self {
+        self.prober = prober;
+        self
+    }
+
+    /// Lists and probes every candidate, whether there's a chip there or
+    /// not. Check [`Descriptor::probe`] to see how it went.
+    ///
+    /// Descriptors are sorted by bus and chip select.
+    pub fn scan(&self) -> Result<Vec<Descriptor>, Error> {
+        let mut found = self.spidevs()?;
+        #[cfg(feature = "iio")]
+        found.extend(self.iio_devices()?);
+
+        found.sort_by_key(|d| (d.bus, d.chip_select, d.path.clone()));
+        Ok(found)
+    }
+
+    /// Like [`Discovery::scan`], but only keeps the ones with a chip.
+    pub fn find(&self) -> Result<Vec<Descriptor>, Error> {
+        let mut found = self.scan()?;
+        found.retain(Descriptor::is_present);
+        Ok(found)
+    }
+
+    /// Lists and probes every `spidevB.C` in the dev root.
+    fn spidevs(&self) -> Result<Vec<Descriptor>, Error> {
+        Ok(list(&self.dev_root)?
+            .into_iter()
+            .filter_map(|path| {
+                let name = path.file_name()?.to_str()?;
+                let (bus, chip_select) = parse_bus(name.strip_prefix("spidev")?)?;
+
+                Some(Descriptor {
+                    interface: Interface::Spidev,
+                    probe: (self.prober)(&path),
+                    path,
+                    bus: Some(bus),
+                    chip_select: Some(chip_select),
+                    driver: None,
+                })
+            })
+            .collect())
+    }
+
+    /// Lists and probes every Maxim thermocouple converter bound to an IIO
+    /// driver.
+    #[cfg(feature = "iio")]
+    fn iio_devices(&self) -> Result<Vec<Descriptor>, Error> {
+        let devices = crate::backend::iio::devices_in(&self.sys_root)?;
+
+        Ok(devices
+            .into_iter()
+            .map(|mut device| {
+                // the device lives under its SPI device (`spiB.C`) in sysfs
+                let bus = fs::canonicalize(device.path()).ok().and_then(|path| {
+                    let spi = path.parent()?.file_name()?.to_str()?;
+                    parse_bus(spi.strip_prefix("spi")?)
+                });
+
+                let probe = crate::read(&mut device)
+                    .and_then(Reading::from_raw)
+                    .and_then(|reading| match reading.is_open() {
+                        true => Err(Error::OpenCircuit),
+                        false => Ok(reading),
+                    });
+
+                Descriptor {
+                    interface: Interface::Iio,
+                    path: device.path().to_path_buf(),
+                    bus: bus.map(|(bus, _)| bus),
+                    chip_select: bus.map(|(_, cs)| cs),
+                    driver: Some(device.name().to_string()),
+                    probe,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Lists a directory, or nothing if it isn't there.
+fn list(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    match fs::read_dir(dir) {
+        Ok(entries) => Ok(entries.filter_map(|e| e.ok()).map(|e| e.path()).collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(io_error(&format!("list `{}`", dir.display()), e)),
+    }
+}
+
+/// Parses `B.C` into a bus and chip select.
+fn parse_bus(s: &str) -> Option<(u32, u32)> {
+    let (bus, chip_select) = s.split_once('.')?;
+    Some((bus.parse().ok()?, chip_select.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pretends `spidev0.1` has a MAX6675, and nothing else does.
+    fn fake_prober(path: &Path) -> Result<Reading, Error> {
+        match path.file_name().and_then(|n| n.to_str()) {
+            Some("spidev0.1") => Reading::from_raw(400 << 3),
+            _ => Err(Error::DeviceNotPresent { raw: 0xFFFF }),
+        }
+    }
+
+    #[test]
+    fn finds_spidevs() {
+        let root = tempfile::tempdir().unwrap();
+        let dev = root.path().join("dev");
+        fs::create_dir_all(&dev).unwrap();
+        for name in ["spidev1.0", "spidev0.1", "spidev0.0", "spidevx", "ttyS0"] {
+            fs::write(dev.join(name), "").unwrap();
+        }
+
+        let discovery = Discovery::new()
+            .with_dev_root(&dev)
+            .with_sys_root(root.path().join("sys"))
+            .with_prober(fake_prober);
+
+        let scanned = discovery.scan().unwrap();
+        let buses: Vec<_> = scanned.iter().map(|d| (d.bus, d.chip_select)).collect();
+        assert_eq!(
+            buses,
+            [(Some(0), Some(0)), (Some(0), Some(1)), (Some(1), Some(0))]
+        );
+
+        let found = discovery.find().unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, dev.join("spidev0.1"));
+        assert_eq!(found[0].probe.as_ref().map(|r| r.celsius()), Ok(100.0));
+    }
+
+    #[test]
+    fn nothing_there_is_fine() {
+        let root = tempfile::tempdir().unwrap();
+        let discovery = Discovery::new()
+            .with_dev_root(root.path().join("dev"))
+            .with_sys_root(root.path().join("sys"));
+
+        assert_eq!(discovery.scan().unwrap(), []);
+    }
+
+    #[cfg(feature = "iio")]
+    #[test]
+    fn finds_iio_devices() {
+        use std::os::unix::fs::symlink;
+
+        let root = tempfile::tempdir().unwrap();
+        let sys = root.path().join("sys");
+
+        // sysfs links each IIO device to where it really lives, under its
+        // SPI device
+        let add = |spi: &str, n: usize, attributes: &[(&str, &str)]| {
+            let real = sys.join(format!("devices/platform/soc/spi0/{spi}/iio:device{n}"));
+            fs::create_dir_all(&real).unwrap();
+            for (attribute, value) in attributes {
+                fs::write(real.join(attribute), value).unwrap();
+            }
+
+            let devices = sys.join("bus/iio/devices");
+            fs::create_dir_all(&devices).unwrap();
+            symlink(&real, devices.join(format!("iio:device{n}"))).unwrap();
+        };
+
+        add(
+            "spi0.1",
+            0,
+            &[
+                ("name", "max6675\n"),
+                ("in_temp_raw", "100\n"),
+                ("in_temp_scale", "250\n"),
+            ],
+        );
+        add(
+            "spi0.0",
+            1,
+            &[("name", "max31855k\n"), ("in_temp_scale", "250\n")],
+        );
+        add("spi0.2", 2, &[("name", "bme280\n")]);
+
+        let discovery = Discovery::new()
+            .with_dev_root(root.path().join("dev"))
+            .with_sys_root(&sys);
+
+        let scanned = discovery.scan().unwrap();
+        let drivers: Vec<_> = scanned.iter().map(|d| d.driver.as_deref()).collect();
+        assert_eq!(drivers, [Some("max31855k"), Some("max6675")]);
+
+        // the MAX31855 doesn't have an `in_temp_raw`, so it can't be read
+        let found = discovery.find().unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].interface, Interface::Iio);
+        assert_eq!((found[0].bus, found[0].chip_select), (Some(0), Some(1)));
+        assert_eq!(found[0].probe.as_ref().map(|r| r.celsius()), Ok(25.0));
+    }
+}
@@ -0,0 +1,384 @@
+//! # filter
+//!
+//! Smoothing for noisy readings.
+//!
+//! The MAX6675 only resolves quarter degrees, and real rigs tend to jitter
+//! around by half a degree or so. These filters take the edge off:
+//!
+//! - [`MovingAverage`]: the mean of the last few readings.
+//! - [`Median`]: the median of the last few readings. Great at ignoring spikes.
+//! - [`Ewma`]: an exponentially weighted moving average.
+//! - [`Kalman`]: a one-dimensional Kalman filter.
+//!
+//! Filters chain with [`Filter::then`], and [`Filtered`] (or
+//! [`Sensor::filtered`]) puts them in front of a sensor.
+//!
+//! ## Example
+//!
+//! ```no_run
+//!
+//! use linux_max6675::{filter::{Filter, Median, MovingAverage}, Max6675, Sensor};
+//!
+//! // throw out spikes first, then smooth out what's left
+//! let mut max = Max6675::new("/dev/spidev0.0")
+//!     .unwrap()
+//!     .filtered(Median::new(5).then(MovingAverage::new(4)));
+//!
+//! loop {
+//!     println!("it's about {}° C", max.read_celsius().unwrap());
+//! }
+//!
+//! ```
+
+use std::collections::VecDeque;
+
+use crate::{Error, Sensor};
+
+/// Turns a stream of readings into a smoother one.
+pub trait Filter {
+    /// Adds a new reading, and returns the filtered value.
+    fn update(&mut self, value: f64) -> f64;
+
+    /// Forgets everything, as if no readings had come in yet.
+    fn reset(&mut self);
+
+    /// Feeds this filter's output into `next`.
+    fn then<F: Filter>(self, next: F) -> Chain<Self, F>
+    where
+        Self: Sized,
+    {
+        Chain {
+            first: self,
+            second: next,
+        }
+    }
+}
+
+impl<F: Filter + ?Sized> Filter for Box<F> {
+    fn update(&mut self, value: f64) -> f64 {
+        (**self).update(value)
+    }
+
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+}
+
+/// Two filters, one after the other. See [`Filter::then`].
+#[derive(Clone, Debug)]
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: Filter, B: Filter> Filter for Chain<A, B> {
+    fn update(&mut self, value: f64) -> f64 {
+        self.second.update(self.first.update(value))
+    }
+
+    fn reset(&mut self) {
+        self.first.reset();
+        self.second.reset();
+    }
+}
+
+/// The mean of the last `window` readings.
+///
+/// Until the window fills up, it's the mean of however many there are.
+#[derive(Clone, Debug)]
+pub struct MovingAverage {
+    window: VecDeque<f64>,
+    size: usize,
+}
+
+impl MovingAverage {
+    /// Averages over the last `size` readings.
+    ///
+    /// Panics if `size` is zero.
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "a moving average needs a window of at least one");
+        Self {
+            window: VecDeque::with_capacity(size),
+            size,
+        }
+    }
+}
+
+impl Filter for MovingAverage {
+    fn update(&mut self, value: f64) -> f64 {
+        if self.window.len() == self.size {
+            self.window.pop_front();
+        }
+        self.window.push_back(value);
+
+        // re-summing stops rounding errors from piling up forever
+        self.window.iter().sum::<f64>() / self.window.len() as f64
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+    }
+}
+
+/// The median of the last `window` readings.
+///
+/// A single wild reading can't drag the median around like it can a mean, so
+/// this is the one to use for spikes. With an even number of readings, it's
+/// the mean of the middle two.
+#[derive(Clone, Debug)]
+pub struct Median {
+    window: VecDeque<f64>,
+    size: usize,
+}
+
+impl Median {
+    /// Takes the median of the last `size` readings.
+    ///
+    /// Panics if `size` is zero.
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "a median needs a window of at least one");
+        Self {
+            window: VecDeque::with_capacity(size),
+            size,
+        }
+    }
+}
+
+impl Filter for Median {
+    // `is_multiple_of` would bump our minimum Rust version to 1.87, just for
+    // this
+    #[allow(unknown_lints, clippy::manual_is_multiple_of)]
+    fn update(&mut self, value: f64) -> f64 {
+        if self.window.len() == self.size {
+            self.window.pop_front();
+        }
+        self.window.push_back(value);
+
+        let mut sorted: Vec<f64> = self.window.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+
+        let middle = sorted.len() / 2;
+        if sorted.len() % 2 == 0 {
+            (sorted[middle - 1] + sorted[middle]) / 2.0
+        } else {
+            sorted[middle]
+        }
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+    }
+}
+
+/// An exponentially weighted moving average.
+///
+/// Each reading moves the output `alpha` of the way towards it, so smaller
+/// `alpha`s are smoother but slower to catch up.
+#[derive(Clone, Debug)]
+pub struct Ewma {
+    alpha: f64,
+    value: Option<f64>,
+}
+
+impl Ewma {
+    /// Creates an EWMA that weighs new readings by `alpha`.
+    ///
+    /// Panics unless `0 < alpha <= 1`.
+    pub fn new(alpha: f64) -> Self {
+        assert!(
+            alpha > 0.0 && alpha <= 1.0,
+            "an EWMA's alpha has to be in (0, 1]"
+        );
+        Self { alpha, value: None }
+    }
+}
+
+impl Filter for Ewma {
+    fn update(&mut self, value: f64) -> f64 {
+        let next = match self.value {
+            Some(last) => last + self.alpha * (value - last),
+            None => value,
+        };
+
+        self.value = Some(next);
+        next
+    }
+
+    fn reset(&mut self) {
+        self.value = None;
+    }
+}
+
+/// A one-dimensional Kalman filter, for a temperature that drifts slowly.
+///
+/// `process_noise` is how much the real temperature might change between
+/// readings (as a variance, in °C²), and `measurement_noise` is how noisy the
+/// readings are. The bigger `measurement_noise` is compared to
+/// `process_noise`, the smoother (and slower) the output.
+#[derive(Clone, Debug)]
+pub struct Kalman {
+    process_noise: f64,
+    measurement_noise: f64,
+    /// Our estimate, and how unsure we are about it.
+    estimate: Option<(f64, f64)>,
+}
+
+impl Kalman {
+    /// Creates a Kalman filter with the given noise variances.
+    ///
+    /// Panics if either is negative, or if `measurement_noise` is zero.
+    pub fn new(process_noise: f64, measurement_noise: f64) -> Self {
+        assert!(process_noise >= 0.0, "process noise can't be negative");
+        assert!(
+            measurement_noise > 0.0,
+            "measurement noise has to be positive"
+        );
+        Self {
+            process_noise,
+            measurement_noise,
+            estimate: None,
+        }
+    }
+}
+
+impl Filter for Kalman {
+    fn update(&mut self, value: f64) -> f64 {
+        let Some((estimate, variance)) = self.estimate else {
+            // we've got nothing better to go on yet
+            self.estimate = Some((value, self.measurement_noise));
+            return value;
+        };
+
+        let variance = variance + self.process_noise;
+        let gain = variance / (variance + self.measurement_noise);
+        let estimate = estimate + gain * (value - estimate);
+
+        self.estimate = Some((estimate, (1.0 - gain) * variance));
+        estimate
+    }
+
+    fn reset(&mut self) {
+        self.estimate = None;
+    }
+}
+
+/// A sensor with a filter in front of it.
+///
+/// Errors are passed along as-is, and don't touch the filter.
+#[derive(Clone, Debug)]
+pub struct Filtered<S, F> {
+    sensor: S,
+    filter: F,
+}
+
+impl<S: Sensor, F: Filter> Filtered<S, F> {
+    /// Runs `sensor`'s readings through `filter`.
+    pub fn new(sensor: S, filter: F) -> Self {
+        Self { sensor, filter }
+    }
+
+    /// Tries to read the filtered temperature in Celsius.
+    pub fn read_celsius(&mut self) -> Result<f64, Error> {
+        Ok(self.filter.update(self.sensor.read_celsius()?))
+    }
+
+    /// The sensor underneath.
+    pub fn sensor_mut(&mut self) -> &mut S {
+        &mut self.sensor
+    }
+
+    /// The filter.
+    pub fn filter_mut(&mut self) -> &mut F {
+        &mut self.filter
+    }
+
+    /// Gives back the sensor and the filter.
+    pub fn into_inner(self) -> (S, F) {
+        (self.sensor, self.filter)
+    }
+}
+
+impl<S: Sensor, F: Filter> Sensor for Filtered<S, F> {
+    fn read_celsius(&mut self) -> Result<f64, Error> {
+        Filtered::read_celsius(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{backend::mock::MockMax6675, Max6675};
+
+    fn run(filter: &mut impl Filter, values: &[f64]) -> Vec<f64> {
+        values.iter().map(|v| filter.update(*v)).collect()
+    }
+
+    #[test]
+    fn moving_average() {
+        let mut filter = MovingAverage::new(3);
+        assert_eq!(
+            run(&mut filter, &[3.0, 6.0, 9.0, 12.0]),
+            [3.0, 4.5, 6.0, 9.0]
+        );
+
+        filter.reset();
+        assert_eq!(filter.update(1.0), 1.0);
+    }
+
+    #[test]
+    fn median_ignores_spikes() {
+        let mut filter = Median::new(3);
+        assert_eq!(
+            run(&mut filter, &[20.0, 21.0, 500.0, 21.0, 22.0]),
+            [20.0, 20.5, 21.0, 21.0, 22.0]
+        );
+    }
+
+    #[test]
+    fn ewma() {
+        let mut filter = Ewma::new(0.5);
+        assert_eq!(run(&mut filter, &[10.0, 20.0, 20.0]), [10.0, 15.0, 17.5]);
+    }
+
+    #[test]
+    fn kalman_settles_on_a_steady_value() {
+        let mut filter = Kalman::new(0.001, 0.25);
+
+        // ±0.5° of jitter around 100°
+        let jittery: Vec<f64> = (0..200)
+            .map(|i| if i % 2 == 0 { 100.5 } else { 99.5 })
+            .collect();
+        let out = run(&mut filter, &jittery);
+
+        assert_eq!(out[0], 100.5);
+        assert!(out[199] > 99.9 && out[199] < 100.1, "{}", out[199]);
+    }
+
+    #[test]
+    fn chains_in_order() {
+        let mut filter = Median::new(3).then(MovingAverage::new(2));
+        assert_eq!(run(&mut filter, &[10.0, 500.0, 10.0]), [10.0, 132.5, 132.5]);
+
+        // a `Box<dyn Filter>` works too
+        let mut boxed: Box<dyn Filter> = Box::new(Ewma::new(1.0));
+        assert_eq!(boxed.update(7.0), 7.0);
+    }
+
+    #[test]
+    fn filters_a_driver() {
+        let mut spi = MockMax6675::new();
+        spi.push_profile([20.0, 20.5])
+            .push_open_circuit()
+            .push_celsius(21.0);
+        let mut max = Max6675::from_spi(spi);
+        max.set_conversion_time(Duration::ZERO);
+
+        let mut filtered = max.filtered(MovingAverage::new(3));
+        assert_eq!(filtered.read_celsius(), Ok(20.0));
+        assert_eq!(filtered.read_celsius(), Ok(20.25));
+        assert_eq!(filtered.read_celsius(), Err(Error::OpenCircuit));
+        assert_eq!(filtered.read_celsius(), Ok(20.5));
+    }
+}
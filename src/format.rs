@@ -0,0 +1,348 @@
+//! # format
+//!
+//! One schema for readings, in a few different formats.
+//!
+//! A [`Record`] is everything we know about a single read: which sensor it
+//! came from, when, the raw word, the decoded fault bits, and the temperature
+//! (or what went wrong). It can be rendered as InfluxDB line protocol, a CSV
+//! row, or JSON.
+//!
+//! ## Example
+//!
+//! ```no_run
+//!
+//! use linux_max6675::{format::Record, Max6675};
+//!
+//! let mut max = Max6675::new("/dev/spidev0.0").unwrap();
+//!
+//! println!("{}", Record::CSV_HEADER);
+//! loop {
+//!     let record = Record::now("furnace", &max.read_frame());
+//!     println!("{}", record.to_csv());
+//! }
+//!
+//! ```
+
+use std::{
+    fmt::Write as _,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Reading};
+
+/// The fault bits in a raw word.
+///
+/// Only `open_circuit` happens in normal use. The other two should always be
+/// low, so if they aren't, something's wrong with the wiring.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Faults {
+    /// D2: the thermocouple is open.
+    pub open_circuit: bool,
+    /// D15: the dummy sign bit.
+    pub sign_bit: bool,
+    /// D1: the device ID bit.
+    pub device_id: bool,
+}
+
+impl Faults {
+    /// Decodes the fault bits from a raw word.
+    pub fn from_raw(raw: u16) -> Self {
+        let reading = Reading::from_raw_unchecked(raw);
+        Self {
+            open_circuit: reading.is_open(),
+            sign_bit: reading.sign_bit(),
+            device_id: reading.device_id(),
+        }
+    }
+}
+
+/// One read from one sensor.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// When the read happened. Serialized as seconds since the UNIX epoch.
+    #[serde(with = "epoch_seconds")]
+    pub timestamp: SystemTime,
+    /// Whatever you call the sensor.
+    pub sensor: String,
+    /// The raw word, if the chip sent one.
+    pub raw: Option<u16>,
+    /// The temperature, if there is one.
+    pub celsius: Option<f64>,
+    /// The fault bits from `raw`. All low if there's no raw word.
+    pub faults: Faults,
+    /// What went wrong, if anything.
+    pub error: Option<Error>,
+}
+
+impl Record {
+    /// The header for [`Record::to_csv`].
+    pub const CSV_HEADER: &'static str =
+        "timestamp,sensor,raw,celsius,open_circuit,sign_bit,device_id,error";
+
+    /// Makes a record from the result of [`Max6675::read_frame`](crate::Max6675::read_frame).
+    ///
+    /// Open thermocouples get an [`Error::OpenCircuit`], but keep their raw
+    /// word. So do impossible frames and implausible readings.
+    pub fn new(
+        sensor: impl Into<String>,
+        timestamp: SystemTime,
+        result: &Result<Reading, Error>,
+    ) -> Self {
+        let (raw, celsius, error) = match result {
+            Ok(reading) if reading.is_open() => {
+                (Some(reading.raw()), None, Some(Error::OpenCircuit))
+            }
+            Ok(reading) => (Some(reading.raw()), Some(reading.celsius()), None),
+            Err(
+                e @ (Error::InvalidFrame { raw }
+                | Error::Implausible { raw }
+                | Error::DeviceNotPresent { raw }),
+            ) => (Some(*raw), None, Some(e.clone())),
+            Err(e) => (None, None, Some(e.clone())),
+        };
+
+        Self {
+            timestamp,
+            sensor: sensor.into(),
+            raw,
+            celsius,
+            faults: raw.map(Faults::from_raw).unwrap_or_default(),
+            error,
+        }
+    }
+
+    /// Like [`Record::new`], timestamped right now.
+    pub fn now(sensor: impl Into<String>, result: &Result<Reading, Error>) -> Self {
+        Self::new(sensor, SystemTime::now(), result)
+    }
+
+    /// The timestamp as a [`std::time::Duration`] since the UNIX epoch.
+    fn since_epoch(&self) -> std::time::Duration {
+        self.timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+
+    /// Renders the record as InfluxDB line protocol, in the `max6675`
+    /// measurement with nanosecond timestamps.
+    pub fn to_influx(&self) -> String {
+        let mut line = format!("max6675,sensor={} ", influx_tag(&self.sensor));
+
+        // writing to a `String` can't fail
+        if let Some(raw) = self.raw {
+            let _ = write!(line, "raw={raw}i,");
+        }
+        if let Some(celsius) = self.celsius {
+            let _ = write!(line, "celsius={celsius},");
+        }
+        let _ = write!(
+            line,
+            "open_circuit={},sign_bit={},device_id={}",
+            self.faults.open_circuit, self.faults.sign_bit, self.faults.device_id
+        );
+        if let Some(error) = &self.error {
+            let _ = write!(line, ",error=\"{}\"", influx_string(&error.to_string()));
+        }
+
+        let _ = write!(line, " {}", self.since_epoch().as_nanos());
+        line
+    }
+
+    /// Renders the record as a CSV row. See [`Record::CSV_HEADER`].
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{:.3},{},{},{},{},{},{},{}",
+            self.since_epoch().as_secs_f64(),
+            csv_field(&self.sensor),
+            self.raw.map(|raw| raw.to_string()).unwrap_or_default(),
+            self.celsius.map(|c| c.to_string()).unwrap_or_default(),
+            self.faults.open_circuit,
+            self.faults.sign_bit,
+            self.faults.device_id,
+            self.error
+                .as_ref()
+                .map(|e| csv_field(&e.to_string()))
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Renders the record as a line of JSON.
+    pub fn to_json(&self) -> String {
+        // a `Record` is always valid JSON
+        serde_json::to_string(self).expect("records should serialize")
+    }
+}
+
+/// Quotes a CSV field, if it needs it.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Escapes an InfluxDB tag value.
+fn influx_tag(tag: &str) -> String {
+    let mut escaped = String::with_capacity(tag.len());
+    for c in tag.chars() {
+        if matches!(c, ',' | '=' | ' ' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escapes an InfluxDB string field (without the quotes).
+fn influx_string(field: &str) -> String {
+    field.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// (De)serializes a [`SystemTime`] as fractional seconds since the UNIX epoch.
+mod epoch_seconds {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, s: S) -> Result<S::Ok, S::Error> {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        s.serialize_f64(since_epoch.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<SystemTime, D::Error> {
+        let secs = f64::deserialize(d)?;
+        Duration::try_from_secs_f64(secs)
+            .map(|since_epoch| UNIX_EPOCH + since_epoch)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// (De)serializes an [`embedded_hal::spi::ErrorKind`] by name, since
+/// embedded-hal doesn't do serde.
+pub(crate) mod error_kind {
+    use embedded_hal::spi::ErrorKind;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(kind: &ErrorKind, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(match kind {
+            ErrorKind::Overrun => "overrun",
+            ErrorKind::ModeFault => "mode_fault",
+            ErrorKind::FrameFormat => "frame_format",
+            ErrorKind::ChipSelectFault => "chip_select_fault",
+            // `ErrorKind` is non-exhaustive, so anything new ends up here
+            _ => "other",
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<ErrorKind, D::Error> {
+        Ok(match String::deserialize(d)?.as_str() {
+            "overrun" => ErrorKind::Overrun,
+            "mode_fault" => ErrorKind::ModeFault,
+            "frame_format" => ErrorKind::FrameFormat,
+            "chip_select_fault" => ErrorKind::ChipSelectFault,
+            _ => ErrorKind::Other,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use embedded_hal::spi::ErrorKind;
+
+    use super::*;
+    use crate::Temperature;
+
+    fn at(result: Result<Reading, Error>) -> Record {
+        let timestamp = UNIX_EPOCH + Duration::from_millis(1_700_000_000_250);
+        Record::new("kiln, top", timestamp, &result)
+    }
+
+    #[test]
+    fn formats_good_readings() {
+        let record = at(Reading::from_raw(0x0320));
+
+        assert_eq!(
+            record.to_influx(),
+            "max6675,sensor=kiln\\,\\ top raw=800i,celsius=25,open_circuit=false,sign_bit=false,device_id=false 1700000000250000000"
+        );
+        assert_eq!(
+            record.to_csv(),
+            "1700000000.250,\"kiln, top\",800,25,false,false,false,"
+        );
+        assert_eq!(
+            record.to_json(),
+            r#"{"timestamp":1700000000.25,"sensor":"kiln, top","raw":800,"celsius":25.0,"faults":{"open_circuit":false,"sign_bit":false,"device_id":false},"error":null}"#
+        );
+    }
+
+    #[test]
+    fn formats_faults() {
+        let open = at(Reading::from_raw(0x0004));
+        assert_eq!(open.raw, Some(0x0004));
+        assert_eq!(open.celsius, None);
+        assert!(open.faults.open_circuit);
+        assert_eq!(open.error, Some(Error::OpenCircuit));
+        assert!(open.to_influx().contains(",open_circuit=true,"));
+        assert!(open.to_influx().contains(",error=\""));
+
+        let invalid = at(Reading::from_raw(0x8002));
+        assert_eq!(invalid.raw, Some(0x8002));
+        assert!(invalid.faults.sign_bit && invalid.faults.device_id);
+        assert!(invalid
+            .to_csv()
+            .starts_with("1700000000.250,\"kiln, top\",32770,,false,true,true,"));
+
+        let nothing = at(Err(Error::ReceivedNothing));
+        assert_eq!(nothing.raw, None);
+        assert_eq!(nothing.faults, Faults::default());
+        assert!(nothing
+            .to_influx()
+            .starts_with("max6675,sensor=kiln\\,\\ top open_circuit=false,"));
+    }
+
+    #[test]
+    fn records_round_trip() {
+        for result in [
+            Reading::from_raw(0x0320),
+            Reading::from_raw(0x0004),
+            Err(Error::SPI {
+                kind: ErrorKind::ModeFault,
+                message: "oops".into(),
+            }),
+            Err(Error::NotReady {
+                remaining: Duration::from_millis(20),
+            }),
+        ] {
+            let record = at(result);
+            let json = record.to_json();
+            assert_eq!(
+                serde_json::from_str::<Record>(&json).unwrap(),
+                record,
+                "{json}"
+            );
+        }
+    }
+
+    #[test]
+    fn readings_are_checked_when_deserialized() {
+        assert_eq!(
+            serde_json::to_string(&Reading::from_raw(0x0320).unwrap()).unwrap(),
+            "800"
+        );
+        assert_eq!(
+            serde_json::from_str::<Reading>("800").unwrap().raw(),
+            0x0320
+        );
+        assert!(serde_json::from_str::<Reading>("32768").is_err());
+
+        assert_eq!(
+            serde_json::to_string(&Temperature::from_quarters(401)).unwrap(),
+            "401"
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//! # health
+//!
+//! Keeps an eye on the thermocouple connection.
+//!
+//! A loose connector on something that vibrates opens and closes all day, and
+//! a single open-circuit frame (bit D2) doesn't mean the probe is gone.
+//! [`HealthMonitor`] counts open circuits, rides out short blips by handing
+//! back the last good reading, and keeps track of how healthy the connection
+//! is. You can register callbacks to hear about it when that changes.
+//!
+//! ## Example
+//!
+//! ```no_run
+//!
+//! use linux_max6675::{health::HealthMonitor, Max6675};
+//!
+//! let mut max = HealthMonitor::new(Max6675::new("/dev/spidev0.0").unwrap());
+//! max.set_open_after(5);
+//! max.on_change(|from, to| eprintln!("thermocouple went from {from:?} to {to:?}"));
+//!
+//! loop {
+//!     match max.read_celsius() {
+//!         Ok(celsius) => println!("{celsius}° C ({:?})", max.state()),
+//!         Err(e) => eprintln!("{e}"),
+//!     }
+//! }
+//!
+//! ```
+
+use std::{collections::VecDeque, fmt};
+
+use embedded_hal::spi::SpiDevice;
+
+use crate::{Error, Max6675, Sensor};
+
+/// How the thermocouple connection is doing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Health {
+    /// No open circuits lately.
+    #[default]
+    Healthy,
+    /// There've been open circuits lately, but not enough in a row to call it
+    /// open. Probably a loose connector.
+    Intermittent,
+    /// The thermocouple's been open for several reads in a row.
+    Open,
+    /// We can't talk to the chip at all.
+    NoDevice,
+}
+
+/// Something to call when the health changes, with the old and new states.
+type Callback = Box<dyn FnMut(Health, Health) + Send>;
+
+/// A [`Max6675`] that debounces open circuits and tracks the connection's
+/// [`Health`].
+///
+/// - An open circuit only counts as [`Health::Open`] once it's lasted
+///   `open_after` reads in a row (3, to start with). Until then, the monitor
+///   is [`Health::Intermittent`], and [`HealthMonitor::read_celsius`] gives
+///   back the last good reading instead of an error (if there is one).
+/// - After the connection closes again, it stays intermittent until there
+///   haven't been any opens in the last `window` reads (10, to start with).
+/// - SPI errors, short reads and impossible frames `open_after` times in a row
+///   mean [`Health::NoDevice`], and so does [`Error::DeviceNotPresent`] right
+///   away. These errors are never debounced.
+///
+/// [`Error::NotReady`] doesn't count for anything.
+pub struct HealthMonitor<SPI> {
+    max: Max6675<SPI>,
+    open_after: usize,
+    window: usize,
+    /// Whether each of the last `window` reads was open, newest last.
+    recent: VecDeque<bool>,
+    consecutive_opens: usize,
+    total_opens: u64,
+    consecutive_failures: usize,
+    state: Health,
+    /// The last good reading, in ° C.
+    last_good: Option<f64>,
+    callbacks: Vec<Callback>,
+}
+
+impl<SPI: fmt::Debug> fmt::Debug for HealthMonitor<SPI> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HealthMonitor")
+            .field("max", &self.max)
+            .field("open_after", &self.open_after)
+            .field("window", &self.window)
+            .field("consecutive_opens", &self.consecutive_opens)
+            .field("total_opens", &self.total_opens)
+            .field("state", &self.state)
+            .field("last_good", &self.last_good)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<SPI> HealthMonitor<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    /// Wraps a driver, which we assume is healthy to start with.
+    pub fn new(max: Max6675<SPI>) -> Self {
+        Self {
+            max,
+            open_after: 3,
+            window: 10,
+            recent: VecDeque::new(),
+            consecutive_opens: 0,
+            total_opens: 0,
+            consecutive_failures: 0,
+            state: Health::Healthy,
+            last_good: None,
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// How many open circuits (or failures) in a row it takes to believe it.
+    ///
+    /// Panics if `open_after` is zero.
+    pub fn set_open_after(&mut self, open_after: usize) {
+        assert!(open_after > 0, "it takes at least one open to be open");
+        self.open_after = open_after;
+    }
+
+    /// How many reads without an open circuit it takes to go from
+    /// intermittent back to healthy.
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window;
+        while self.recent.len() > window {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Calls `callback` with the old and new states whenever the health
+    /// changes.
+    pub fn on_change(&mut self, callback: impl FnMut(Health, Health) + Send + 'static) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// How the connection is doing.
+    pub fn state(&self) -> Health {
+        self.state
+    }
+
+    /// How many open circuits we've seen in a row, up to now.
+    pub fn consecutive_opens(&self) -> usize {
+        self.consecutive_opens
+    }
+
+    /// How many open circuits we've seen, ever.
+    pub fn total_opens(&self) -> u64 {
+        self.total_opens
+    }
+
+    /// The driver underneath.
+    pub fn inner_mut(&mut self) -> &mut Max6675<SPI> {
+        &mut self.max
+    }
+
+    /// Gives back the driver.
+    pub fn into_inner(self) -> Max6675<SPI> {
+        self.max
+    }
+
+    /// Moves to `state`, letting everyone know if that's a change.
+    fn transition(&mut self, state: Health) {
+        let from = std::mem::replace(&mut self.state, state);
+        if from != state {
+            for callback in &mut self.callbacks {
+                callback(from, state);
+            }
+        }
+    }
+
+    /// Remembers whether the latest read was open.
+    fn remember(&mut self, open: bool) {
+        if self.window == 0 {
+            return;
+        }
+        if self.recent.len() == self.window {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(open);
+    }
+
+    /// Tries to read the temperature in Celsius, riding out short open
+    /// circuits.
+    ///
+    /// While an open circuit is still being debounced, this gives back the
+    /// last good reading. Once it's been open for `open_after` reads, or if
+    /// there's no last good reading, you'll get [`Error::OpenCircuit`].
+    pub fn read_celsius(&mut self) -> Result<f64, Error> {
+        let reading = match self.max.read_frame() {
+            Ok(reading) => reading,
+            Err(e @ Error::NotReady { .. }) => return Err(e),
+            Err(e) => {
+                self.consecutive_failures += 1;
+                if self.consecutive_failures >= self.open_after
+                    || matches!(e, Error::DeviceNotPresent { .. })
+                {
+                    self.transition(Health::NoDevice);
+                }
+                return Err(e);
+            }
+        };
+
+        self.consecutive_failures = 0;
+        self.remember(reading.is_open());
+
+        if reading.is_open() {
+            self.consecutive_opens += 1;
+            self.total_opens += 1;
+
+            if self.consecutive_opens >= self.open_after {
+                self.transition(Health::Open);
+                return Err(Error::OpenCircuit);
+            }
+
+            self.transition(Health::Intermittent);
+            return self.last_good.ok_or(Error::OpenCircuit);
+        }
+
+        self.consecutive_opens = 0;
+        if self.recent.contains(&true) {
+            self.transition(Health::Intermittent);
+        } else {
+            self.transition(Health::Healthy);
+        }
+
+        let celsius = self.max.corrected_celsius(reading.temperature());
+        self.last_good = Some(celsius);
+        Ok(celsius)
+    }
+}
+
+impl<SPI> Sensor for HealthMonitor<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    fn read_celsius(&mut self) -> Result<f64, Error> {
+        HealthMonitor::read_celsius(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::backend::mock::MockMax6675;
+
+    fn monitor(mock: MockMax6675) -> HealthMonitor<MockMax6675> {
+        let mut max = Max6675::from_spi(mock);
+        max.set_conversion_time(Duration::ZERO);
+        HealthMonitor::new(max)
+    }
+
+    #[test]
+    fn rides_out_short_opens() {
+        let mut mock = MockMax6675::new();
+        mock.push_celsius(20.0)
+            .push_open_circuit()
+            .push_open_circuit()
+            .push_celsius(20.5);
+
+        let mut max = monitor(mock);
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(max.consecutive_opens(), 2);
+        assert_eq!(max.state(), Health::Intermittent);
+
+        assert_eq!(max.read_celsius(), Ok(20.5));
+        assert_eq!(max.consecutive_opens(), 0);
+        assert_eq!(max.total_opens(), 2);
+        assert_eq!(max.state(), Health::Intermittent);
+    }
+
+    #[test]
+    fn goes_open_after_enough_in_a_row() {
+        let mut mock = MockMax6675::new();
+        mock.push_celsius(20.0);
+        for _ in 0..3 {
+            mock.push_open_circuit();
+        }
+
+        let mut max = monitor(mock);
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(max.read_celsius(), Err(Error::OpenCircuit));
+        assert_eq!(max.state(), Health::Open);
+    }
+
+    #[test]
+    fn without_a_good_reading_opens_are_errors() {
+        let mut mock = MockMax6675::new();
+        mock.push_open_circuit();
+
+        let mut max = monitor(mock);
+        assert_eq!(max.read_celsius(), Err(Error::OpenCircuit));
+        assert_eq!(max.state(), Health::Intermittent);
+    }
+
+    #[test]
+    fn heals_after_a_quiet_window() {
+        let mut mock = MockMax6675::new();
+        mock.push_open_circuit().push_profile([20.0, 20.0, 20.0]);
+
+        let mut max = monitor(mock);
+        max.set_window(3);
+
+        let _ = max.read_celsius();
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(max.state(), Health::Intermittent);
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(max.state(), Health::Healthy);
+    }
+
+    #[test]
+    fn notices_a_missing_chip() {
+        let mut mock = MockMax6675::new();
+        mock.push_celsius(20.0).push_short_read().push_short_read();
+
+        let mut max = monitor(mock);
+        max.set_open_after(2);
+
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(max.read_celsius(), Err(Error::ReceivedNothing));
+        assert_eq!(max.state(), Health::Healthy);
+        assert_eq!(max.read_celsius(), Err(Error::ReceivedNothing));
+        assert_eq!(max.state(), Health::NoDevice);
+    }
+
+    #[test]
+    fn calls_back_on_changes() {
+        let mut mock = MockMax6675::new();
+        mock.push_celsius(20.0)
+            .push_open_circuit()
+            .push_open_circuit()
+            .push_celsius(20.0)
+            .push_celsius(20.0);
+
+        let mut max = monitor(mock);
+        max.set_open_after(2);
+        max.set_window(1);
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let heard = changes.clone();
+        max.on_change(move |from, to| heard.lock().unwrap().push((from, to)));
+
+        for _ in 0..5 {
+            let _ = max.read_celsius();
+        }
+
+        assert_eq!(
+            *changes.lock().unwrap(),
+            [
+                (Health::Healthy, Health::Intermittent),
+                (Health::Intermittent, Health::Open),
+                (Health::Open, Health::Healthy),
+            ]
+        );
+    }
+}
@@ -5,47 +5,542 @@
 //! ## Usage
 //!
 //! To use this library, you'll need to know which SPI device to select.
-//! On Linux, you can use `ls /dev -1 | grep spidev` to figure it out!
+//! On Linux, you can use `ls /dev -1 | grep spidev` to figure it out! Or,
+//! let [`discover::Discovery`] look for you: it probes every spidev (and,
+//! with the `iio` feature, every IIO thermocouple) and tells you which bus and
+//! chip select each MAX6675 is on.
 //!
 //! Then, you can use something like this example in your binary...
 //!
 //! ```no_run
 //!
-//! use rppal::spi::{ Spi, Bus, SlaveSelect, Mode };
+//! use linux_max6675::Max6675;
 //! use std::time::Duration;
 //!
-//! let mut tc = Spi::new(
-//!     Bus::Spi0,
-//!     SlaveSelect::Ss0,
-//!     1_000_000,
-//!     Mode::Mode1
-//! ).unwrap();
+//! let mut max = Max6675::new("/dev/spidev0.0").unwrap();
 //!
 //! std::thread::sleep(Duration::from_secs(3));
 //!
 //! loop {
-//!     let celsius = linux_max6675::read_celsius(&mut tc).unwrap();
+//!     // this waits for the chip to finish converting, so no need to sleep!
+//!     let celsius = max.read_celsius().unwrap();
 //!     println!("Read Celsius! Got: {}° C.", celsius);
-//!     std::thread::sleep(Duration::from_millis(500));
 //! };
 //!
 //! ```
+//!
+//! ## Other converters
+//!
+//! The [`converter`] module drives the MAX6675's siblings too: the MAX6674,
+//! MAX31855 and MAX31856. They all implement [`ThermocoupleConverter`], so
+//! one codebase can read whichever is on the board.
+//!
+//! ## Filters
+//!
+//! The [`filter`] module has moving averages, medians, EWMAs and a Kalman
+//! filter for smoothing out noisy readings. They chain together, and wrap
+//! anything that implements [`Sensor`].
+//!
+//! ## Calibration
+//!
+//! If your probe reads a little off, attach a [`Calibration`] (an offset, a
+//! gain, and/or a table of measured vs. actual temperatures) with
+//! [`Max6675::set_calibration`]. Calibrations can be saved and loaded as JSON
+//! with the `serde` feature, or TOML with `toml`.
+//!
+//! ## Linearization
+//!
+//! The MAX6675 treats type K thermocouples as perfectly linear, which they
+//! aren't. Use [`Max6675::set_linearization`] with
+//! [`Linearization::NistTypeK`] to correct readings with NIST's ITS-90
+//! polynomials. This happens before any calibration.
+//!
+//! ## Validation
+//!
+//! Noise on SO can make for some wild single readings. [`validate::Validator`]
+//! rejects readings that are out of range or change too fast, re-reading a
+//! few times before giving up with [`Error::Implausible`].
+//!
+//! ## Health
+//!
+//! Loose connectors make for open circuits that come and go.
+//! [`health::HealthMonitor`] debounces them, counts them, and tracks whether
+//! the connection is healthy, intermittent, open, or missing a chip entirely,
+//! with callbacks for when that changes.
+//!
+//! ## Sampling
+//!
+//! If several parts of your program want the temperature, hand the sensor to
+//! a [`sampler::Sampler`]. It reads on a schedule in a background thread, and
+//! its handles give anyone the latest timestamped reading without waiting.
+//!
+//! ## Backends
+//!
+//! The driver works with anything that implements
+//! [`embedded_hal::spi::SpiDevice`], so you can bring your own HAL. If you
+//! don't have one handy, these come with the crate:
+//!
+//! - `spidev` (default): [`Spidev`], which talks to the kernel's spidev
+//!   interface directly. This works on pretty much any Linux board.
+//! - `rppal`: [`RppalSpi`], which uses the [`rppal`](https://docs.rs/rppal) crate.
+//! - [`BitBangSpi`](backend::bitbang::BitBangSpi) bit-bangs SPI over any
+//!   three GPIO pins, for boards without a free SPI controller. Enable `gpio`
+//!   to use Linux's GPIO character devices (`/dev/gpiochipN`) for it.
+//! - `iio`: `backend::iio::Iio`, for chips bound to the kernel's
+//!   `maxim_thermocouple` IIO driver instead of spidev.
+//! - `mock`: `MockMax6675`, a scripted pretend chip for testing your code
+//!   without any hardware.
+//!
+//! ## Async
+//!
+//! With the `async` feature, `asynch::Max6675` works with
+//! [`embedded-hal-async`](https://docs.rs/embedded-hal-async) and awaits the
+//! chip's conversion time instead of blocking. Add the `tokio` feature for an
+//! async spidev backend that runs on tokio.
+//!
+//! ## Serialization
+//!
+//! With the `serde` feature, [`Reading`], [`Temperature`] and [`Error`] can be
+//! serialized, and `format::Record` renders a reading as InfluxDB line
+//! protocol, CSV, or JSON, so everyone downstream gets the same schema.
+//!
+//! ## Daemon
+//!
+//! With the `daemon` feature, you get `max6675d`, which polls the sensors
+//! listed in a TOML config and logs their readings to stdout, rotating CSV
+//! files, JSON lines, or a UNIX socket. See `daemon::Config` for the format.
+//!
+//! ## Metrics
+//!
+//! The `metrics` feature adds a small Prometheus exporter. Record readings
+//! with `metrics::Metrics`, then serve them at `/metrics` with
+//! `metrics::Exporter`. If you're using the daemon, add a `prometheus` sink
+//! instead.
+//!
+//! ## MQTT
+//!
+//! The `mqtt` feature adds `mqtt::Publisher`, which sends readings and
+//! open-circuit status to an MQTT broker, along with Home Assistant discovery
+//! configs so your sensors show up on their own.
 
-use rppal::spi::Spi;
+use std::{
+    any::Any,
+    time::{Duration, Instant},
+};
+
+use embedded_hal::spi::{ErrorKind, SpiDevice};
 use thiserror::Error;
 
+mod array;
+#[cfg(feature = "async")]
+pub mod asynch;
+pub mod backend;
+pub mod calibration;
+pub mod converter;
+#[cfg(feature = "daemon")]
+pub mod daemon;
+#[cfg(feature = "spidev")]
+pub mod discover;
+pub mod filter;
+#[cfg(feature = "serde")]
+pub mod format;
+pub mod health;
+pub mod linearization;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+mod reading;
+pub mod sampler;
+mod sensor;
+mod temperature;
+pub mod validate;
+
+pub use array::Max6675Array;
+pub use calibration::Calibration;
+pub use converter::ThermocoupleConverter;
+pub use linearization::Linearization;
+pub use reading::Reading;
+pub use sensor::Sensor;
+pub use temperature::Temperature;
+
+#[cfg(feature = "rppal")]
+pub use backend::rppal::RppalSpi;
+#[cfg(feature = "spidev")]
+pub use backend::spidev::Spidev;
+
+/// The SPI clock speed used when opening a MAX6675.
+///
+/// The chip tops out at 4.3 MHz (see MAX6675 datasheet, p. 3), so we stay
+/// comfortably below that.
+pub const CLOCK_SPEED: u32 = 1_000_000;
+
+/// How long the MAX6675 needs to finish a conversion after CS goes high.
+///
+/// Reading any sooner either gives you the last value again or aborts the
+/// conversion entirely (see MAX6675 datasheet, p. 2).
+pub const CONVERSION_TIME: Duration = Duration::from_millis(220);
+
+/// How many frames [`Max6675::probe`] reads.
+pub const PROBE_READS: usize = 3;
+
+/// What a [`Max6675`] should do when you read before its conversion is done.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ConversionPolicy {
+    /// Sleep until the conversion is done, then read.
+    #[default]
+    Block,
+    /// Fail with [`Error::NotReady`].
+    Error,
+    /// Give back the last reading again without touching the chip.
+    ///
+    /// If there's no last reading yet, this blocks instead.
+    Cached,
+}
+
 /// An error emitted due to problems with the MAX6675.
-#[derive(Debug, Error)]
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Error {
-    #[error("Error using the provided SPI. See rppal::spi::Error: {source}")]
+    #[error("Error using the provided SPI ({kind}): {message}")]
     SPI {
-        #[from]
-        source: rppal::spi::Error,
+        #[cfg_attr(feature = "serde", serde(with = "format::error_kind"))]
+        kind: ErrorKind,
+        message: String,
     },
     #[error("The MAX6675 detected an open circuit (bit D2 was high). Please check the thermocouple connection and try again.")]
     OpenCircuit,
     #[error("The SPI bus received nothing. Please check your SPI bus and CS and try again.")]
     ReceivedNothing,
+    #[error("`{path}` isn't a spidev path. Please use something like `/dev/spidev0.0`.")]
+    InvalidPath { path: String },
+    #[error("The MAX6675 sent an impossible frame ({raw:#06x}). Please check your wiring and SPI mode and try again.")]
+    InvalidFrame { raw: u16 },
+    #[error("The MAX6675 is still converting. Please try again in {remaining:?}.")]
+    NotReady { remaining: Duration },
+    #[error("The MAX6675 kept sending implausible readings (last was {raw:#06x}). Please check for noise on SO and try again.")]
+    Implausible { raw: u16 },
+    #[error("There doesn't seem to be a MAX6675 connected (it kept sending {raw:#06x}). Please check its wiring and power and try again.")]
+    DeviceNotPresent { raw: u16 },
+    #[error("The converter reported a fault: {fault}. Please check the thermocouple wiring and try again.")]
+    Fault { fault: converter::Fault },
+}
+
+impl Error {
+    /// Turns an error from any SPI implementation into one of ours.
+    ///
+    /// Backends that already speak in terms of our [`Error`] (like the ones in
+    /// [`backend`]) are passed through untouched, so you'll still see
+    /// [`Error::ReceivedNothing`] and friends.
+    fn from_spi<E: embedded_hal::spi::Error + 'static>(e: E) -> Self {
+        if let Some(ours) = (&e as &dyn Any).downcast_ref::<Error>() {
+            return ours.clone();
+        }
+
+        Error::SPI {
+            kind: e.kind(),
+            message: format!("{e:?}"),
+        }
+    }
+}
+
+impl embedded_hal::spi::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::SPI { kind, .. } => *kind,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// A MAX6675 connected over SPI.
+///
+/// This owns its SPI device, so you can just keep it around and read from
+/// it whenever you'd like.
+///
+/// It also keeps track of when the chip's last conversion started, so you
+/// won't get stale duplicates by reading too quickly. See [`ConversionPolicy`]
+/// for your options there.
+///
+/// ## Example
+///
+/// ```no_run
+///
+/// use linux_max6675::Max6675;
+///
+/// let mut max = Max6675::new("/dev/spidev0.0").unwrap();
+///
+/// println!("it's {}° celsius in here!", max.read_celsius().unwrap());
+/// println!("...or {}° fahrenheit, if you prefer.", max.read_fahrenheit().unwrap());
+///
+/// ```
+#[derive(Debug)]
+pub struct Max6675<SPI> {
+    spi: SPI,
+    policy: ConversionPolicy,
+    conversion_time: Duration,
+    /// When CS last went high, kicking off a new conversion.
+    last_read: Option<Instant>,
+    /// The last word we read, for [`ConversionPolicy::Cached`].
+    cached: Option<u16>,
+    /// How many frames in a row looked disconnected, and how many it takes
+    /// to give up.
+    disconnected: usize,
+    not_present_after: Option<usize>,
+    linearization: Linearization,
+    calibration: Option<Calibration>,
+}
+
+#[cfg(feature = "spidev")]
+impl Max6675<Spidev> {
+    /// Opens the MAX6675 at the given spidev path, like `/dev/spidev0.0`.
+    ///
+    /// The SPI is configured for the chip: mode 1 (CPOL = 0, CPHA = 1) at
+    /// [`CLOCK_SPEED`].
+    pub fn new(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        Ok(Self::from_spi(Spidev::open(path)?))
+    }
+}
+
+impl<SPI> Max6675<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    /// Wraps any SPI device that you've already configured yourself.
+    ///
+    /// Make sure it's in mode 1 and clocked at 4.3 MHz or below!
+    pub fn from_spi(spi: SPI) -> Self {
+        Self {
+            spi,
+            policy: ConversionPolicy::default(),
+            conversion_time: CONVERSION_TIME,
+            last_read: None,
+            cached: None,
+            disconnected: 0,
+            not_present_after: None,
+            linearization: Linearization::None,
+            calibration: None,
+        }
+    }
+
+    /// Gives back the underlying SPI device.
+    pub fn into_inner(self) -> SPI {
+        self.spi
+    }
+
+    /// What happens when you read before a conversion is done.
+    pub fn conversion_policy(&self) -> ConversionPolicy {
+        self.policy
+    }
+
+    /// Changes what happens when you read before a conversion is done.
+    pub fn set_conversion_policy(&mut self, policy: ConversionPolicy) {
+        self.policy = policy;
+    }
+
+    /// Changes how long we wait for a conversion. Defaults to [`CONVERSION_TIME`].
+    ///
+    /// You probably don't need this unless your chip is running slow.
+    pub fn set_conversion_time(&mut self, conversion_time: Duration) {
+        self.conversion_time = conversion_time;
+    }
+
+    /// Makes reads fail with [`Error::DeviceNotPresent`] once this many frames
+    /// in a row look disconnected (see [`looks_disconnected`]), like
+    /// `Some(PROBE_READS)`.
+    ///
+    /// This is off (`None`) by default, since a thermocouple sitting at
+    /// exactly 0° C (say, in an ice bath) reads back all zeros, just like a
+    /// floating SO line. Only turn it on if your probe won't sit at 0° C.
+    pub fn set_not_present_after(&mut self, not_present_after: Option<usize>) {
+        self.not_present_after = not_present_after;
+    }
+
+    /// The calibration applied to readings, if any.
+    pub fn calibration(&self) -> Option<&Calibration> {
+        self.calibration.as_ref()
+    }
+
+    /// Corrects every reading from here on with `calibration`. `None` goes
+    /// back to what the chip says.
+    ///
+    /// This affects [`Max6675::read_celsius`] and friends, but not
+    /// [`Max6675::read_temperature`] or anything rawer than that.
+    pub fn set_calibration(&mut self, calibration: Option<Calibration>) {
+        self.calibration = calibration;
+    }
+
+    /// How readings are corrected for the thermocouple's non-linearity.
+    pub fn linearization(&self) -> Linearization {
+        self.linearization
+    }
+
+    /// Changes how readings are corrected for the thermocouple's
+    /// non-linearity. This happens before the calibration.
+    ///
+    /// Like [`Max6675::set_calibration`], this affects [`Max6675::read_celsius`]
+    /// and friends, but not [`Max6675::read_temperature`].
+    pub fn set_linearization(&mut self, linearization: Linearization) {
+        self.linearization = linearization;
+    }
+
+    /// Whether readings get corrected at all.
+    fn is_corrected(&self) -> bool {
+        self.linearization != Linearization::None || self.calibration.is_some()
+    }
+
+    /// Converts a temperature from the chip to Celsius, corrected by the
+    /// linearization and then the calibration.
+    pub fn corrected_celsius(&self, temp: Temperature) -> f64 {
+        let celsius = self.linearization.apply(temp.celsius());
+
+        match &self.calibration {
+            Some(calibration) => calibration.apply(celsius),
+            None => celsius,
+        }
+    }
+
+    /// How long until the current conversion is done.
+    ///
+    /// This is zero if the chip is ready to read.
+    pub fn time_until_ready(&self) -> Duration {
+        self.last_read.map_or(Duration::ZERO, |last| {
+            self.conversion_time.saturating_sub(last.elapsed())
+        })
+    }
+
+    /// Tries to return the thermocouple's raw data. See [`read`] for more info.
+    ///
+    /// This respects the [`ConversionPolicy`]. If you've turned it on with
+    /// [`Max6675::set_not_present_after`], it also fails with
+    /// [`Error::DeviceNotPresent`] once enough frames in a row look like
+    /// nothing's connected.
+    pub fn read_raw(&mut self) -> Result<u16, Error> {
+        let remaining = self.time_until_ready();
+
+        if !remaining.is_zero() {
+            match (self.policy, self.cached) {
+                (ConversionPolicy::Error, _) => return Err(Error::NotReady { remaining }),
+                (ConversionPolicy::Cached, Some(cached)) => return Ok(cached),
+                _ => std::thread::sleep(remaining),
+            }
+        }
+
+        let bytes = read(&mut self.spi)?;
+
+        // CS just went high, so a new conversion has started
+        self.last_read = Some(Instant::now());
+        self.cached = Some(bytes);
+
+        if !looks_disconnected(bytes) {
+            self.disconnected = 0;
+            return Ok(bytes);
+        }
+
+        self.disconnected += 1;
+        match self.not_present_after {
+            Some(after) if self.disconnected >= after => {
+                Err(Error::DeviceNotPresent { raw: bytes })
+            }
+            _ => Ok(bytes),
+        }
+    }
+
+    /// Makes sure there's really a MAX6675 on the other end, with a
+    /// thermocouple attached. Handy at startup, to catch wiring mistakes.
+    ///
+    /// This reads [`PROBE_READS`] fresh frames, waiting out the conversion
+    /// time before each one (whatever the [`ConversionPolicy`]), so it takes
+    /// a little while. It fails with:
+    ///
+    /// - [`Error::DeviceNotPresent`] if every frame looked disconnected. A
+    ///   chip at exactly 0° C does too, so don't probe in an ice bath!
+    /// - [`Error::InvalidFrame`] if only some of them were impossible, which
+    ///   usually means a noisy or half-connected SO line.
+    /// - [`Error::OpenCircuit`] if the chip's there, but the thermocouple isn't.
+    ///
+    /// Otherwise, you get the last frame.
+    pub fn probe(&mut self) -> Result<Reading, Error> {
+        let mut frames = [0; PROBE_READS];
+        for frame in &mut frames {
+            std::thread::sleep(self.time_until_ready());
+            *frame = read(&mut self.spi)?;
+            self.last_read = Some(Instant::now());
+            self.cached = Some(*frame);
+        }
+
+        self.disconnected = frames
+            .iter()
+            .rev()
+            .take_while(|raw| looks_disconnected(**raw))
+            .count();
+        if self.disconnected == PROBE_READS {
+            return Err(Error::DeviceNotPresent {
+                raw: frames[PROBE_READS - 1],
+            });
+        }
+
+        for raw in frames {
+            Reading::from_raw(raw)?;
+        }
+
+        let reading = Reading::from_raw_unchecked(frames[PROBE_READS - 1]);
+        if reading.is_open() {
+            return Err(Error::OpenCircuit);
+        }
+        Ok(reading)
+    }
+
+    /// Tries to read and decode a whole frame from the thermocouple.
+    ///
+    /// Fails with [`Error::InvalidFrame`] if the frame couldn't have come from
+    /// a MAX6675. An open thermocouple is *not* an error here - check
+    /// [`Reading::is_open`] yourself.
+    pub fn read_frame(&mut self) -> Result<Reading, Error> {
+        Reading::from_raw(self.read_raw()?)
+    }
+
+    /// Tries to read the thermocouple's exact temperature.
+    pub fn read_temperature(&mut self) -> Result<Temperature, Error> {
+        let reading = self.read_frame()?;
+        // Check if MAX6675 terminals are open
+        if reading.is_open() {
+            return Err(Error::OpenCircuit);
+        }
+        Ok(reading.temperature())
+    }
+
+    /// Tries to read the thermocouple's temperature in Celsius.
+    pub fn read_celsius(&mut self) -> Result<f64, Error> {
+        let temp = self.read_temperature()?;
+        Ok(self.corrected_celsius(temp))
+    }
+
+    /// Tries to read the thermocouple's temperature in Fahrenheit.
+    pub fn read_fahrenheit(&mut self) -> Result<f64, Error> {
+        let temp = self.read_temperature()?;
+
+        // without any corrections, `Temperature` can do it exactly
+        Ok(if self.is_corrected() {
+            self.corrected_celsius(temp) * 9.0 / 5.0 + 32.0
+        } else {
+            temp.fahrenheit()
+        })
+    }
+
+    /// Tries to read the thermocouple's temperature in Kelvin.
+    pub fn read_kelvin(&mut self) -> Result<f64, Error> {
+        let temp = self.read_temperature()?;
+
+        Ok(if self.is_corrected() {
+            self.corrected_celsius(temp) + 273.15
+        } else {
+            temp.kelvin()
+        })
+    }
 }
 
 /// Tries to return the thermocouple's raw data for data science. (and other fun little things)
@@ -54,18 +549,17 @@ pub enum Error {
 ///
 /// Refer to page 5 of [Maxim Integrated's MAX6675 specsheet](https://www.analog.com/media/en/technical-documentation/data-sheets/MAX6675.pdf)
 /// for info on how to interpret this raw data.
-pub fn read(spi: &mut Spi) -> Result<u16, Error> {
+pub fn read<SPI>(spi: &mut SPI) -> Result<u16, Error>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
     // Create 2 bytes buffer
     let mut buf = [0_u8; 2];
     // Read bytes from SPI
-    let len = spi.read(&mut buf)?;
-    if len == 2 {
-        // Return bytes as u16
-        Ok(u16::from_be_bytes(buf))
-    } else {
-        // No bytes read
-        Err(Error::ReceivedNothing)
-    }
+    spi.read(&mut buf).map_err(Error::from_spi)?;
+    // Return bytes as u16
+    Ok(u16::from_be_bytes(buf))
 }
 
 /// Check if MAX6675 terminals are open.
@@ -79,32 +573,67 @@ pub fn read(spi: &mut Spi) -> Result<u16, Error> {
 ///
 /// ```no_run
 ///
-/// use rppal::spi::{ Spi, Bus, SlaveSelect, Mode };
+/// use linux_max6675::Spidev;
 ///
-/// let mut tc = Spi::new(
-///     Bus::Spi0,
-///     SlaveSelect::Ss0,
-///     1_000_000,
-///     Mode::Mode1
-/// ).unwrap();
+/// let mut tc = Spidev::open("/dev/spidev0.0").unwrap();
 ///
 /// let bytes = linux_max6675::read(&mut tc).unwrap();
 ///
 /// if linux_max6675::is_open(bytes) {
-///     println("thermocouple is open!")
+///     println!("thermocouple is open!")
 /// };
 ///
-/// ````
+/// ```
 pub fn is_open(bytes: u16) -> bool {
-   (bytes & 0x04) != 0
+    (bytes & 0x04) != 0
+}
+
+/// Check if the raw data looks like nobody's home.
+///
+/// With no MAX6675 driving it, SO floats, and you tend to read all zeros
+/// (stuck low) or all ones (stuck high). Frames with the dummy sign bit (D15)
+/// or the device ID bit (D1) set can't come from a real chip either.
+///
+/// All zeros is also what a real chip says at exactly 0° C, so one of these
+/// on its own doesn't prove much, and even several in a row might just be an
+/// ice bath. That's why [`Max6675`] only checks if you ask it to.
+pub fn looks_disconnected(bytes: u16) -> bool {
+    bytes == 0x0000 || Reading::from_raw(bytes).is_err()
 }
 
 /// Parse temperature from bytes
 ///
-/// Extracts 12 bit integer from D14-D3 and multiply it by 1/4 precision factor
+/// Extracts 12 bit integer from D14-D3 as a number of quarter degrees
 /// (see MAX6675 datasheet, p. 5)
+pub fn parse_temperature(bytes: u16) -> Temperature {
+    // 12 bits can't go above 4095, so this always fits
+    Temperature::from_quarters((0x0FFF & (bytes >> 3)) as i16)
+}
+
+/// Parse temperature from bytes, in Celsius
+///
+/// See [`parse_temperature`] for the exact version.
 pub fn parse_celsius(bytes: u16) -> f64 {
-    ((0x1FFF & (bytes >> 3)) as f64) * 0.25
+    parse_temperature(bytes).celsius()
+}
+
+/// Tries to read the thermocouple's exact temperature.
+///
+/// Fails if the thermocouple is open, or if the frame couldn't have come from
+/// a MAX6675.
+pub fn read_temperature<SPI>(spi: &mut SPI) -> Result<Temperature, Error>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    // Read bytes from SPI, making sure they came from a MAX6675
+    let reading = Reading::from_raw(read(spi)?)?;
+    // Check if MAX6675 terminals are open
+    if reading.is_open() {
+        return Err(Error::OpenCircuit);
+    }
+    // Parse temperature from bytes
+    Ok(reading.temperature())
 }
 
 /// Tries to read the thermocouple's temperature in Celsius.
@@ -113,27 +642,269 @@ pub fn parse_celsius(bytes: u16) -> f64 {
 ///
 /// ```no_run
 ///
-/// use rppal::spi::{ Spi, Bus, SlaveSelect, Mode };
+/// use linux_max6675::Spidev;
 ///
-/// let mut tc = Spi::new(
-///     Bus::Spi0,
-///     SlaveSelect::Ss0,
-///     1_000_000,
-///     Mode::Mode1
-/// ).unwrap();
+/// let mut tc = Spidev::open("/dev/spidev0.0").unwrap();
 ///
 /// let celsius = linux_max6675::read_celsius(&mut tc).unwrap();
 ///
 /// println!("it's {}° celsius in here!", celsius);
 ///
 /// ```
-pub fn read_celsius(spi: &mut Spi) -> Result<f64, Error> {
-    // Read bytes from SPI
-    let bytes = read(spi)?;
-    // Check if MAX6675 terminals are open
-    is_open(bytes)
-        .then(|| Err(Error::OpenCircuit))
-        .map_or(Ok(()), |e| e)?;
-    // Parse temperature from bytes
-    Ok(parse_celsius(bytes))
+pub fn read_celsius<SPI>(spi: &mut SPI) -> Result<f64, Error>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    Ok(read_temperature(spi)?.celsius())
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_hal::spi::ErrorKind;
+
+    use super::*;
+    use crate::backend::mock::MockMax6675;
+
+    #[test]
+    fn parses_celsius() {
+        assert_eq!(parse_celsius(0x0000), 0.0);
+        assert_eq!(parse_celsius(0b0000_0000_1000_0000), 4.0);
+        assert_eq!(parse_celsius(0b0000_0000_1010_1000), 5.25);
+        assert_eq!(parse_celsius(0x7FF8), 1023.75);
+        assert_eq!(parse_celsius(0xFFF8), 1023.75);
+    }
+
+    #[test]
+    fn parses_every_word_exactly() {
+        for bytes in 0..=u16::MAX {
+            let temp = parse_temperature(bytes);
+            let quarters = (bytes >> 3) & 0x0FFF;
+
+            // only D14-D3 make it in. D15 shouldn't leak through!
+            assert_eq!(temp.quarters(), quarters as i16);
+            assert_eq!(parse_temperature(bytes & 0x7FF8), temp);
+
+            let celsius = parse_celsius(bytes);
+            assert!((0.0..=1023.75).contains(&celsius), "{bytes:#06x}");
+            assert_eq!(celsius * 4.0, f64::from(quarters));
+            assert_eq!(f64::from(temp.celsius_f32()), celsius);
+            assert!((temp.fahrenheit() - (celsius * 1.8 + 32.0)).abs() < 1e-9);
+            assert_eq!(temp.kelvin(), celsius + 273.15);
+        }
+    }
+
+    #[test]
+    fn detects_open_circuits() {
+        assert!(is_open(0x0004));
+        assert!(is_open(0x7FFC));
+        assert!(!is_open(0x7FF8));
+        assert!(!is_open(0x0003));
+    }
+
+    #[test]
+    fn reads_raw_words() {
+        let mut mock = MockMax6675::new();
+        mock.push_frame(0x1234);
+
+        assert_eq!(read(&mut mock), Ok(0x1234));
+        assert_eq!(mock.reads(), 1);
+    }
+
+    #[test]
+    fn reads_celsius() {
+        let mut mock = MockMax6675::new();
+        mock.push_profile([20.0, 20.25, 1023.75]);
+
+        assert_eq!(read_celsius(&mut mock), Ok(20.0));
+        assert_eq!(read_celsius(&mut mock), Ok(20.25));
+        assert_eq!(read_celsius(&mut mock), Ok(1023.75));
+    }
+
+    #[test]
+    fn invalid_frames_are_an_error() {
+        let mut mock = MockMax6675::new();
+        mock.push_frame(0xFFFF).push_frame(0x0152);
+        let mut max = Max6675::from_spi(mock);
+        max.set_conversion_time(Duration::ZERO);
+
+        assert_eq!(max.read_celsius(), Err(Error::InvalidFrame { raw: 0xFFFF }));
+        assert_eq!(max.read_frame(), Err(Error::InvalidFrame { raw: 0x0152 }));
+    }
+
+    #[test]
+    fn open_circuit_is_an_error() {
+        let mut mock = MockMax6675::new();
+        mock.push_open_circuit();
+
+        assert_eq!(read_celsius(&mut mock), Err(Error::OpenCircuit));
+    }
+
+    #[test]
+    fn short_reads_receive_nothing() {
+        let mut mock = MockMax6675::new();
+        mock.push_short_read();
+
+        assert_eq!(read(&mut mock), Err(Error::ReceivedNothing));
+        // an empty script is just as quiet
+        assert_eq!(read(&mut mock), Err(Error::ReceivedNothing));
+    }
+
+    #[test]
+    fn spi_errors_keep_their_kind() {
+        let mut mock = MockMax6675::new();
+        mock.push_spi_error(ErrorKind::ChipSelectFault);
+
+        assert!(matches!(
+            read_celsius(&mut mock),
+            Err(Error::SPI {
+                kind: ErrorKind::ChipSelectFault,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn notices_a_floating_bus() {
+        for stuck in [0x0000, 0xFFFF] {
+            let mut mock = MockMax6675::new();
+            for _ in 0..PROBE_READS {
+                mock.push_frame(stuck);
+            }
+            mock.push_celsius(20.0);
+            let mut max = Max6675::from_spi(mock);
+            max.set_conversion_time(Duration::ZERO);
+            max.set_not_present_after(Some(PROBE_READS));
+
+            for _ in 1..PROBE_READS {
+                assert_eq!(max.read_raw(), Ok(stuck));
+            }
+            assert_eq!(max.read_raw(), Err(Error::DeviceNotPresent { raw: stuck }));
+            // and it comes back as soon as the chip does
+            assert_eq!(max.read_celsius(), Ok(20.0));
+        }
+
+        // invalid device ID bits count too, but a good frame resets the count
+        let mut mock = MockMax6675::new();
+        mock.push_frame(0x0152)
+            .push_frame(0x0000)
+            .push_celsius(20.0)
+            .push_frame(0x0000);
+        let mut max = Max6675::from_spi(mock);
+        max.set_conversion_time(Duration::ZERO);
+        max.set_not_present_after(Some(PROBE_READS));
+
+        assert_eq!(max.read_frame(), Err(Error::InvalidFrame { raw: 0x0152 }));
+        assert_eq!(max.read_celsius(), Ok(0.0));
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(max.read_celsius(), Ok(0.0));
+    }
+
+    #[test]
+    fn ice_baths_arent_missing_chips() {
+        let mut mock = MockMax6675::new();
+        mock.push_profile([0.0; 5]).push_frame(0xFFFF);
+        let mut max = Max6675::from_spi(mock);
+        max.set_conversion_time(Duration::ZERO);
+
+        // steady 0° C is fine by default...
+        for _ in 0..5 {
+            assert_eq!(max.read_celsius(), Ok(0.0));
+        }
+        // ...and so is one stuck-high frame, which is just invalid
+        assert_eq!(max.read_frame(), Err(Error::InvalidFrame { raw: 0xFFFF }));
+    }
+
+    #[test]
+    fn probes_for_the_chip() {
+        let probe = |setup: &dyn Fn(&mut MockMax6675)| {
+            let mut mock = MockMax6675::new();
+            setup(&mut mock);
+            let mut max = Max6675::from_spi(mock);
+            max.set_conversion_time(Duration::ZERO);
+            max.probe()
+        };
+
+        let found = probe(&|mock| {
+            mock.push_profile([20.0, 20.25, 20.5]);
+        });
+        assert_eq!(found.map(|r| r.celsius()), Ok(20.5));
+
+        let missing = probe(&|mock| {
+            mock.push_frame(0xFFFF)
+                .push_frame(0x0000)
+                .push_frame(0xFFFF);
+        });
+        assert_eq!(missing, Err(Error::DeviceNotPresent { raw: 0xFFFF }));
+
+        let noisy = probe(&|mock| {
+            mock.push_celsius(20.0)
+                .push_frame(0x0152)
+                .push_celsius(20.0);
+        });
+        assert_eq!(noisy, Err(Error::InvalidFrame { raw: 0x0152 }));
+
+        let open = probe(&|mock| {
+            mock.push_celsius(20.0)
+                .push_celsius(20.0)
+                .push_open_circuit();
+        });
+        assert_eq!(open, Err(Error::OpenCircuit));
+
+        let quiet = probe(&|_| {});
+        assert_eq!(quiet, Err(Error::ReceivedNothing));
+    }
+
+    #[test]
+    fn blocks_until_conversion_is_done() {
+        let mut mock = MockMax6675::new();
+        mock.push_profile([10.0, 11.0]);
+        let mut max = Max6675::from_spi(mock);
+        max.set_conversion_time(Duration::from_millis(30));
+
+        let start = Instant::now();
+        assert_eq!(max.read_celsius(), Ok(10.0));
+        assert_eq!(max.read_celsius(), Ok(11.0));
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn errors_when_not_ready() {
+        let mut mock = MockMax6675::new();
+        mock.push_profile([10.0, 11.0]);
+        let mut max = Max6675::from_spi(mock);
+        max.set_conversion_policy(ConversionPolicy::Error);
+        max.set_conversion_time(Duration::from_millis(30));
+
+        assert_eq!(max.read_celsius(), Ok(10.0));
+        assert!(matches!(max.read_celsius(), Err(Error::NotReady { .. })));
+
+        std::thread::sleep(max.time_until_ready());
+        assert_eq!(max.read_celsius(), Ok(11.0));
+    }
+
+    #[test]
+    fn caches_when_not_ready() {
+        let mut mock = MockMax6675::new();
+        mock.push_profile([10.0, 11.0]);
+        let mut max = Max6675::from_spi(mock);
+        max.set_conversion_policy(ConversionPolicy::Cached);
+
+        assert_eq!(max.read_celsius(), Ok(10.0));
+        assert_eq!(max.read_celsius(), Ok(10.0));
+        assert_eq!(max.into_inner().reads(), 1);
+    }
+
+    #[test]
+    fn driver_converts_units() {
+        let mut mock = MockMax6675::new();
+        mock.push_profile([100.0, 100.0, 100.0]);
+        let mut max = Max6675::from_spi(mock);
+        max.set_conversion_time(Duration::ZERO);
+
+        assert_eq!(max.read_celsius(), Ok(100.0));
+        assert_eq!(max.read_fahrenheit(), Ok(212.0));
+        assert_eq!(max.read_kelvin(), Ok(373.15));
+        assert_eq!(max.into_inner().remaining(), 0);
+    }
 }
@@ -0,0 +1,183 @@
+//! # linearization
+//!
+//! Undoes the MAX6675's straight-line view of type K thermocouples.
+//!
+//! The MAX6675 converts thermocouple voltage to temperature with a fixed
+//! slope of 41 µV/° C (see the MAX6675 datasheet). Real type K
+//! thermocouples aren't quite linear, so readings drift by several degrees
+//! across the chip's range.
+//!
+//! To fix that, we work out the voltage the chip must have seen from its
+//! reading, then turn that voltage back into a temperature with NIST's ITS-90
+//! inverse polynomials for type K.
+//!
+//! This assumes the cold junction (the chip itself) is near 0° C, or at least
+//! that the chip's own cold-junction compensation is linear enough. For a chip
+//! sitting at room temperature, that's good to a fraction of a degree.
+//!
+//! ## Example
+//!
+//! ```
+//!
+//! use linux_max6675::linearization;
+//!
+//! // a type K thermocouple at 800° C makes 33.275 mV, which the MAX6675 reads
+//! // as about 811.5° C
+//! let corrected = linearization::type_k_celsius(811.5);
+//! assert!((corrected - 800.0).abs() < 0.25);
+//!
+//! ```
+
+/// The slope the MAX6675 assumes, in mV/° C.
+pub const MAX6675_MV_PER_C: f64 = 0.041;
+
+/// How a [`Max6675`](crate::Max6675) should correct for the thermocouple's
+/// non-linearity.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Linearization {
+    /// Trust the chip's linear conversion.
+    #[default]
+    None,
+    /// Correct with NIST's ITS-90 type K inverse polynomials.
+    NistTypeK,
+}
+
+impl Linearization {
+    /// Corrects a temperature in ° C from the chip.
+    pub fn apply(self, celsius: f64) -> f64 {
+        match self {
+            Linearization::None => celsius,
+            Linearization::NistTypeK => type_k_celsius(celsius),
+        }
+    }
+}
+
+/// NIST ITS-90 type K inverse coefficients, for 0 to 20.644 mV (0° to 500° C).
+const TYPE_K_LOW: [f64; 10] = [
+    0.0,
+    2.508355e1,
+    7.860106e-2,
+    -2.503131e-1,
+    8.315270e-2,
+    -1.228034e-2,
+    9.804036e-4,
+    -4.413030e-5,
+    1.057734e-6,
+    -1.052755e-8,
+];
+
+/// NIST ITS-90 type K inverse coefficients, for 20.644 to 54.886 mV (500° to
+/// 1372° C).
+const TYPE_K_HIGH: [f64; 7] = [
+    -1.318058e2,
+    4.830222e1,
+    -1.646031,
+    5.464731e-2,
+    -9.650715e-4,
+    8.802193e-6,
+    -3.110810e-8,
+];
+
+/// Where the two type K ranges meet, in mV.
+const TYPE_K_SPLIT_MV: f64 = 20.644;
+
+/// The thermocouple voltage (in mV) the MAX6675 saw to report `celsius`.
+pub fn millivolts(celsius: f64) -> f64 {
+    celsius * MAX6675_MV_PER_C
+}
+
+/// Turns a type K thermocouple voltage (in mV, with the cold junction at
+/// 0° C) into a temperature in ° C.
+///
+/// NIST's polynomials are only defined from 0 to 54.886 mV. Below that, we
+/// use the bottom of the low range, which is close enough for the little bit
+/// of noise you'd see there.
+pub fn type_k_from_millivolts(mv: f64) -> f64 {
+    let coefficients: &[f64] = if mv < TYPE_K_SPLIT_MV {
+        &TYPE_K_LOW
+    } else {
+        &TYPE_K_HIGH
+    };
+
+    // Horner's method
+    coefficients.iter().rev().fold(0.0, |acc, d| acc * mv + d)
+}
+
+/// Corrects a type K temperature from the MAX6675 (like from
+/// [`parse_celsius`](crate::parse_celsius)) using NIST's polynomials.
+pub fn type_k_celsius(celsius: f64) -> f64 {
+    type_k_from_millivolts(millivolts(celsius))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{backend::mock::MockMax6675, Calibration, Max6675};
+
+    /// NIST ITS-90 type K reference table: (° C, mV), cold junction at 0° C.
+    const NIST_TYPE_K: [(f64, f64); 11] = [
+        (0.0, 0.000),
+        (100.0, 4.096),
+        (200.0, 8.138),
+        (300.0, 12.209),
+        (400.0, 16.397),
+        (500.0, 20.644),
+        (600.0, 24.905),
+        (700.0, 29.129),
+        (800.0, 33.275),
+        (900.0, 37.326),
+        (1000.0, 41.276),
+    ];
+
+    #[test]
+    fn matches_the_nist_table() {
+        for (celsius, mv) in NIST_TYPE_K {
+            let inverted = type_k_from_millivolts(mv);
+            // NIST's own inverse is good to about ±0.06°, and the table is
+            // rounded to the µV (~0.025°)
+            assert!(
+                (inverted - celsius).abs() < 0.1,
+                "{mv} mV should be {celsius}° C, got {inverted}"
+            );
+        }
+    }
+
+    #[test]
+    fn corrects_what_the_chip_reports() {
+        for (celsius, mv) in NIST_TYPE_K {
+            // what the MAX6675 would say, to the nearest quarter degree
+            let reported = (mv / MAX6675_MV_PER_C * 4.0).round() / 4.0;
+            let corrected = type_k_celsius(reported);
+
+            // a quarter degree of quantization, plus NIST's error
+            assert!(
+                (corrected - celsius).abs() < 0.25,
+                "reported {reported}° should be {celsius}° C, got {corrected}"
+            );
+        }
+
+        // and it really does need correcting up there
+        assert!((type_k_celsius(800.0) - 800.0).abs() > 5.0);
+    }
+
+    #[test]
+    fn linearizes_then_calibrates() {
+        let mut spi = MockMax6675::new();
+        spi.push_profile([811.5, 811.5, 811.5]);
+        let mut max = Max6675::from_spi(spi);
+        max.set_conversion_time(Duration::ZERO);
+
+        assert_eq!(max.read_celsius(), Ok(811.5));
+
+        max.set_linearization(Linearization::NistTypeK);
+        let linear = max.read_celsius().unwrap();
+        assert!((linear - 800.0).abs() < 0.25, "{linear}");
+
+        max.set_calibration(Some(Calibration::new().with_offset(1.0)));
+        assert_eq!(max.read_celsius(), Ok(linear + 1.0));
+    }
+}
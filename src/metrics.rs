@@ -0,0 +1,345 @@
+//! # metrics
+//!
+//! A tiny Prometheus exporter for your thermocouples.
+//!
+//! [`Metrics`] keeps track of each sensor's latest temperature, when it last
+//! read successfully, and how many of each kind of [`Error`] it's had.
+//! [`Exporter`] serves all of that over HTTP at `/metrics`, ready to be
+//! scraped.
+//!
+//! ## Example
+//!
+//! ```no_run
+//!
+//! use linux_max6675::{metrics::{Exporter, Metrics}, Max6675};
+//!
+//! let metrics = Metrics::new();
+//! let _exporter = Exporter::bind("0.0.0.0:9675", metrics.clone()).unwrap();
+//!
+//! let mut furnace = Max6675::new("/dev/spidev0.0").unwrap();
+//!
+//! loop {
+//!     // errors get counted too, so there's not much to do with them here
+//!     let _ = metrics.read_celsius("furnace", &mut furnace);
+//! }
+//!
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use embedded_hal::spi::SpiDevice;
+
+use crate::{Error, Max6675};
+
+/// Every kind of error we count, as it appears in the `kind` label.
+const ERROR_KINDS: [&str; 9] = [
+    "spi",
+    "open_circuit",
+    "received_nothing",
+    "invalid_path",
+    "invalid_frame",
+    "not_ready",
+    "implausible",
+    "device_not_present",
+    "fault",
+];
+
+/// Where an error goes in [`ERROR_KINDS`].
+fn error_kind(e: &Error) -> usize {
+    match e {
+        Error::SPI { .. } => 0,
+        Error::OpenCircuit => 1,
+        Error::ReceivedNothing => 2,
+        Error::InvalidPath { .. } => 3,
+        Error::InvalidFrame { .. } => 4,
+        Error::NotReady { .. } => 5,
+        Error::Implausible { .. } => 6,
+        Error::DeviceNotPresent { .. } => 7,
+        Error::Fault { .. } => 8,
+    }
+}
+
+/// What we know about one sensor.
+#[derive(Clone, Debug, Default)]
+struct SensorMetrics {
+    celsius: Option<f64>,
+    /// Seconds since the UNIX epoch.
+    last_success: Option<f64>,
+    errors: [u64; ERROR_KINDS.len()],
+}
+
+/// Metrics for any number of sensors, by name.
+///
+/// This is cheap to clone, and every clone shares the same numbers, so you
+/// can hand one to an [`Exporter`] and keep another for recording.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    sensors: Arc<Mutex<BTreeMap<String, SensorMetrics>>>,
+}
+
+impl Metrics {
+    /// Creates an empty set of metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the result of reading a sensor in Celsius.
+    pub fn record(&self, sensor: &str, result: &Result<f64, Error>) {
+        let mut sensors = self.sensors.lock().unwrap_or_else(|e| e.into_inner());
+        let metrics = sensors.entry(sensor.to_string()).or_default();
+
+        match result {
+            Ok(celsius) => {
+                metrics.celsius = Some(*celsius);
+                metrics.last_success = Some(
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs_f64(),
+                );
+            }
+            Err(e) => metrics.errors[error_kind(e)] += 1,
+        }
+    }
+
+    /// Reads a sensor in Celsius, recording how it went.
+    pub fn read_celsius<SPI>(&self, sensor: &str, max: &mut Max6675<SPI>) -> Result<f64, Error>
+    where
+        SPI: SpiDevice,
+        SPI::Error: 'static,
+    {
+        let result = max.read_celsius();
+        self.record(sensor, &result);
+        result
+    }
+
+    /// Renders everything in Prometheus' text format.
+    pub fn render(&self) -> String {
+        let sensors = self.sensors.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+
+        // writing to a `String` can't fail
+        out.push_str(
+            "# HELP max6675_temperature_celsius The latest temperature read from the sensor.\n",
+        );
+        out.push_str("# TYPE max6675_temperature_celsius gauge\n");
+        for (name, metrics) in sensors.iter() {
+            if let Some(celsius) = metrics.celsius {
+                let _ = writeln!(
+                    out,
+                    "max6675_temperature_celsius{{sensor=\"{}\"}} {celsius}",
+                    escape(name)
+                );
+            }
+        }
+
+        out.push_str("# HELP max6675_last_success_timestamp_seconds When the sensor was last read successfully.\n");
+        out.push_str("# TYPE max6675_last_success_timestamp_seconds gauge\n");
+        for (name, metrics) in sensors.iter() {
+            if let Some(last_success) = metrics.last_success {
+                let _ = writeln!(
+                    out,
+                    "max6675_last_success_timestamp_seconds{{sensor=\"{}\"}} {last_success:.3}",
+                    escape(name)
+                );
+            }
+        }
+
+        out.push_str("# HELP max6675_errors_total Errors while reading the sensor, by kind.\n");
+        out.push_str("# TYPE max6675_errors_total counter\n");
+        for (name, metrics) in sensors.iter() {
+            for (kind, count) in ERROR_KINDS.iter().zip(metrics.errors) {
+                let _ = writeln!(
+                    out,
+                    "max6675_errors_total{{sensor=\"{}\",kind=\"{kind}\"}} {count}",
+                    escape(name)
+                );
+            }
+        }
+
+        out
+    }
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serves [`Metrics`] over HTTP at `/metrics`, on a background thread.
+///
+/// The server stops when this is dropped.
+#[derive(Debug)]
+pub struct Exporter {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Exporter {
+    /// Starts serving `metrics` on `addr`, like `"0.0.0.0:9675"`.
+    ///
+    /// Use port `0` to get any free port, then check [`Exporter::local_addr`].
+    pub fn bind(addr: impl ToSocketAddrs, metrics: Metrics) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = std::thread::spawn({
+            let stop = Arc::clone(&stop);
+            move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+
+                    // one bad client shouldn't take the exporter down
+                    if let Ok(stream) = stream {
+                        let _ = respond(stream, &metrics);
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            addr,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// The address the exporter is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for Exporter {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        // poke the listener so it notices
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Answers one HTTP request.
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+
+    // skip the headers, we don't need any of them
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics.render()),
+        (Some("GET"), Some(_)) => ("404 Not Found", "Try /metrics!\n".to_string()),
+        _ => ("405 Method Not Allowed", String::new()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::backend::mock::MockMax6675;
+
+    /// A very small HTTP client.
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_metrics_from_the_mock() {
+        let mut spi = MockMax6675::new();
+        spi.push_celsius(512.25)
+            .push_open_circuit()
+            .push_short_read()
+            .push_open_circuit();
+        let mut max = Max6675::from_spi(spi);
+        max.set_conversion_time(Duration::ZERO);
+
+        let metrics = Metrics::new();
+        let exporter = Exporter::bind("127.0.0.1:0", metrics.clone()).unwrap();
+
+        assert_eq!(metrics.read_celsius("furnace", &mut max), Ok(512.25));
+        for _ in 0..3 {
+            assert!(metrics.read_celsius("furnace", &mut max).is_err());
+        }
+
+        let response = get(exporter.local_addr(), "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\nmax6675_temperature_celsius{sensor=\"furnace\"} 512.25\n"));
+        assert!(response.contains("\nmax6675_last_success_timestamp_seconds{sensor=\"furnace\"} "));
+        assert!(response
+            .contains("\nmax6675_errors_total{sensor=\"furnace\",kind=\"open_circuit\"} 2\n"));
+        assert!(response
+            .contains("\nmax6675_errors_total{sensor=\"furnace\",kind=\"received_nothing\"} 1\n"));
+        assert!(response.contains("\nmax6675_errors_total{sensor=\"furnace\",kind=\"spi\"} 0\n"));
+
+        assert!(get(exporter.local_addr(), "/").starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn sensors_without_a_reading_have_no_gauges() {
+        let metrics = Metrics::new();
+        metrics.record("kiln \"top\"", &Err(Error::ReceivedNothing));
+
+        let rendered = metrics.render();
+        assert!(!rendered.contains("max6675_temperature_celsius{"));
+        assert!(rendered.contains(
+            "max6675_errors_total{sensor=\"kiln \\\"top\\\"\",kind=\"received_nothing\"} 1\n"
+        ));
+    }
+
+    #[test]
+    fn stops_when_dropped() {
+        let exporter = Exporter::bind("127.0.0.1:0", Metrics::new()).unwrap();
+        let addr = exporter.local_addr();
+        drop(exporter);
+
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}
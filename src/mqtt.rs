@@ -0,0 +1,818 @@
+//! # mqtt
+//!
+//! Publishes readings to an MQTT broker, with Home Assistant discovery.
+//!
+//! This is a small MQTT 3.1.1 client that only knows how to publish (at QoS
+//! 0), which is all we need. For each sensor, [`Publisher`] sends:
+//!
+//! - `<prefix>/<sensor>/temperature`: the temperature in Celsius.
+//! - `<prefix>/<sensor>/open`: `ON` if the thermocouple is open, else `OFF`.
+//! - `<prefix>/<sensor>/error`: what went wrong, if reading failed.
+//!
+//! `<prefix>/status` is a retained `online`/`offline` availability topic. The
+//! broker sets it to `offline` for us if we vanish without saying goodbye.
+//!
+//! If the broker goes away (say, it restarts), the next publish connects
+//! again, and sends everything it needs to over again.
+//!
+//! The first time a sensor is published, we also send retained Home Assistant
+//! discovery configs, so it shows up without any YAML.
+//!
+//! ## Example
+//!
+//! ```no_run
+//!
+//! use linux_max6675::{mqtt::{MqttOptions, Publisher}, Max6675};
+//!
+//! let mut mqtt = Publisher::connect("localhost:1883", MqttOptions::new("shop")).unwrap();
+//! let mut furnace = Max6675::new("/dev/spidev0.0").unwrap();
+//!
+//! loop {
+//!     mqtt.publish("furnace", &furnace.read_frame()).unwrap();
+//!     std::thread::sleep(std::time::Duration::from_secs(1));
+//! }
+//!
+//! ```
+
+use std::{
+    collections::HashSet,
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use crate::{Error, Reading};
+
+/// How long to wait on the broker before giving up.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Packet types, already shifted into the high nibble.
+mod packet {
+    pub const CONNECT: u8 = 0x10;
+    pub const CONNACK: u8 = 0x20;
+    pub const PUBLISH: u8 = 0x30;
+    pub const PINGREQ: u8 = 0xC0;
+    pub const PINGRESP: u8 = 0xD0;
+    pub const DISCONNECT: u8 = 0xE0;
+}
+
+/// How to connect, and where to publish.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MqttOptions {
+    /// Our client ID. This also names the device in Home Assistant.
+    pub client_id: String,
+    /// Every topic starts with this. Defaults to `max6675/<client_id>`, with
+    /// anything odd in the client ID swapped for `_`.
+    pub topic_prefix: String,
+    /// Where to send Home Assistant discovery configs, or `None` to skip them.
+    /// Defaults to `homeassistant`.
+    pub discovery_prefix: Option<String>,
+    /// How often the broker should expect to hear from us.
+    pub keep_alive: Duration,
+    pub username: Option<String>,
+    /// Only allowed along with a `username`.
+    pub password: Option<String>,
+}
+
+impl MqttOptions {
+    /// Default options for the given client ID.
+    pub fn new(client_id: impl Into<String>) -> Self {
+        let client_id = client_id.into();
+        Self {
+            topic_prefix: format!("max6675/{}", slug(&client_id)),
+            client_id,
+            discovery_prefix: Some("homeassistant".into()),
+            keep_alive: Duration::from_secs(60),
+            username: None,
+            password: None,
+        }
+    }
+
+    /// The availability topic.
+    pub fn status_topic(&self) -> String {
+        format!("{}/status", self.topic_prefix)
+    }
+
+    /// One of a sensor's topics, like `temperature`.
+    ///
+    /// Sensor names can have wildcards and slashes in them, which would make a
+    /// mess of things, so they're swapped for `_`.
+    pub fn sensor_topic(&self, sensor: &str, topic: &str) -> String {
+        format!("{}/{}/{topic}", self.topic_prefix, slug(sensor))
+    }
+}
+
+/// A connection to an MQTT broker.
+///
+/// There's nothing running in the background, so if you publish less often
+/// than `keep_alive`, call [`Publisher::ping_if_idle`] now and then so the
+/// broker doesn't give up on us.
+///
+/// If publishing fails, we connect again and have one more go, so a broker
+/// restart only costs an error or two.
+///
+/// Dropping this marks us `offline` and disconnects cleanly.
+#[derive(Debug)]
+pub struct Publisher {
+    stream: TcpStream,
+    /// Where the broker is, for connecting again.
+    addrs: Vec<SocketAddr>,
+    options: MqttOptions,
+    /// Sensors we've sent discovery configs for, on this connection.
+    announced: HashSet<String>,
+    /// When we last sent the broker anything (or tried to connect).
+    last_sent: Instant,
+}
+
+impl Publisher {
+    /// Connects to the broker at `addr`, like `"localhost:1883"`, and marks
+    /// us `online`.
+    pub fn connect(addr: impl ToSocketAddrs, options: MqttOptions) -> io::Result<Self> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+
+        let mut publisher = Self {
+            stream: open(&addrs, &options)?,
+            addrs,
+            options,
+            announced: HashSet::new(),
+            last_sent: Instant::now(),
+        };
+        publisher.send(&publisher.options.status_topic(), b"online", true)?;
+        Ok(publisher)
+    }
+
+    /// Drops the connection and connects again, marking us `online`.
+    ///
+    /// Discovery configs are sent again too, with each sensor's next
+    /// publish, in case the broker forgot them.
+    pub fn reconnect(&mut self) -> io::Result<()> {
+        // if this fails, don't try again until we're idle again
+        self.last_sent = Instant::now();
+
+        let _ = self.stream.shutdown(Shutdown::Both);
+        self.stream = open(&self.addrs, &self.options)?;
+        self.announced.clear();
+        self.send(&self.options.status_topic(), b"online", true)
+    }
+
+    /// The options we connected with.
+    pub fn options(&self) -> &MqttOptions {
+        &self.options
+    }
+
+    /// Publishes the result of reading a sensor.
+    ///
+    /// Open thermocouples just set the `open` topic, since there's no
+    /// temperature to send. Other errors go to the `error` topic.
+    pub fn publish(&mut self, sensor: &str, result: &Result<Reading, Error>) -> io::Result<()> {
+        let celsius = match result {
+            Ok(reading) if reading.is_open() => Err(Error::OpenCircuit),
+            Ok(reading) => Ok(reading.celsius()),
+            Err(e) => Err(e.clone()),
+        };
+
+        self.publish_celsius(sensor, &celsius)
+    }
+
+    /// Like [`Publisher::publish`], but for a temperature that's already been
+    /// worked out, like from a calibrated driver's `read_celsius`.
+    pub fn publish_celsius(&mut self, sensor: &str, result: &Result<f64, Error>) -> io::Result<()> {
+        // better to notice the broker's gone now than to lose this reading
+        if self.hung_up() {
+            self.reconnect()?;
+        }
+
+        match self.try_publish(sensor, result) {
+            // a new connection won't make this any shorter
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => Err(e),
+            Err(_) => {
+                // the connection could've broken since we checked, so have
+                // one more go on a new one
+                self.reconnect()?;
+                self.try_publish(sensor, result)
+            }
+            Ok(()) => Ok(()),
+        }
+    }
+
+    /// Publishes a result on the connection we've got.
+    fn try_publish(&mut self, sensor: &str, result: &Result<f64, Error>) -> io::Result<()> {
+        if let Some(discovery) = self.options.discovery_prefix.clone() {
+            if !self.announced.contains(sensor) {
+                self.announce(&discovery, sensor)?;
+                self.announced.insert(sensor.to_string());
+            }
+        }
+
+        let open = matches!(result, Err(Error::OpenCircuit));
+        if matches!(result, Ok(_) | Err(Error::OpenCircuit)) {
+            let payload = if open { "ON" } else { "OFF" };
+            self.send(
+                &self.options.sensor_topic(sensor, "open"),
+                payload.as_bytes(),
+                false,
+            )?;
+        }
+
+        match result {
+            Ok(celsius) => {
+                let topic = self.options.sensor_topic(sensor, "temperature");
+                self.send(&topic, celsius.to_string().as_bytes(), false)?;
+            }
+            Err(Error::OpenCircuit) => (),
+            Err(e) => {
+                let message = e.to_string();
+                self.send(
+                    &self.options.sensor_topic(sensor, "error"),
+                    message.as_bytes(),
+                    false,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lets the broker know we're still here, and waits for it to answer.
+    pub fn ping(&mut self) -> io::Result<()> {
+        self.stream.write_all(&[packet::PINGREQ, 0])?;
+        self.last_sent = Instant::now();
+
+        let mut pingresp = [0; 2];
+        self.stream.read_exact(&mut pingresp)?;
+        if pingresp != [packet::PINGRESP, 0] {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the broker didn't answer with a PINGRESP",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Pings the broker if we haven't sent it anything for half of
+    /// `keep_alive`, and connects again if it doesn't answer.
+    ///
+    /// This is cheap when there's nothing to do, so call it as often as you
+    /// like.
+    pub fn ping_if_idle(&mut self) -> io::Result<()> {
+        let keep_alive = self.options.keep_alive;
+        if keep_alive.is_zero() || self.last_sent.elapsed() < keep_alive / 2 {
+            return Ok(());
+        }
+
+        if self.hung_up() || self.ping().is_err() {
+            return self.reconnect();
+        }
+
+        Ok(())
+    }
+
+    /// Whether the broker has hung up on us, without waiting to find out.
+    fn hung_up(&mut self) -> bool {
+        if self.stream.set_nonblocking(true).is_err() {
+            return true;
+        }
+
+        let mut buf = [0; 64];
+        let hung_up = loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => break true,
+                // nothing should be coming, but don't let it pile up
+                Ok(_) => continue,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break false,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break true,
+            }
+        };
+
+        self.stream.set_nonblocking(false).is_err() || hung_up
+    }
+
+    /// Sends Home Assistant discovery configs for a sensor.
+    fn announce(&mut self, discovery: &str, sensor: &str) -> io::Result<()> {
+        let node = slug(&self.options.client_id);
+        let object = slug(sensor);
+        let device = serde_json::json!({
+            "identifiers": [format!("max6675_{node}")],
+            "name": self.options.client_id,
+            "model": "MAX6675",
+            "manufacturer": "Maxim Integrated",
+        });
+
+        let temperature = serde_json::json!({
+            "name": format!("{sensor} temperature"),
+            "unique_id": format!("max6675_{node}_{object}_temperature"),
+            "state_topic": self.options.sensor_topic(sensor, "temperature"),
+            "availability_topic": self.options.status_topic(),
+            "device_class": "temperature",
+            "state_class": "measurement",
+            "unit_of_measurement": "°C",
+            "device": device,
+        });
+        let open = serde_json::json!({
+            "name": format!("{sensor} open circuit"),
+            "unique_id": format!("max6675_{node}_{object}_open"),
+            "state_topic": self.options.sensor_topic(sensor, "open"),
+            "availability_topic": self.options.status_topic(),
+            "device_class": "problem",
+            "device": device,
+        });
+
+        self.send(
+            &format!("{discovery}/sensor/{node}/{object}_temperature/config"),
+            temperature.to_string().as_bytes(),
+            true,
+        )?;
+        self.send(
+            &format!("{discovery}/binary_sensor/{node}/{object}_open/config"),
+            open.to_string().as_bytes(),
+            true,
+        )
+    }
+
+    /// Publishes a message at QoS 0.
+    fn send(&mut self, topic: &str, payload: &[u8], retain: bool) -> io::Result<()> {
+        let mut body = Vec::with_capacity(topic.len() + payload.len() + 2);
+        push_str(&mut body, topic)?;
+        body.extend_from_slice(payload);
+
+        let header = packet::PUBLISH | u8::from(retain);
+        self.stream.write_all(&frame(header, &body)?)?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        // a clean disconnect means the broker won't send our will, so say it
+        // ourselves
+        let _ = self.send(&self.options.status_topic(), b"offline", true);
+        let _ = self.stream.write_all(&[packet::DISCONNECT, 0]);
+    }
+}
+
+/// Connects to the broker and waits for it to accept us.
+fn open(addrs: &[SocketAddr], options: &MqttOptions) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "the broker's address didn't resolve to anything",
+    );
+
+    for addr in addrs {
+        match TcpStream::connect_timeout(addr, TIMEOUT) {
+            Ok(stream) => return handshake(stream, options),
+            Err(e) => last_error = e,
+        }
+    }
+
+    Err(last_error)
+}
+
+/// Sends a CONNECT and checks the CONNACK.
+fn handshake(mut stream: TcpStream, options: &MqttOptions) -> io::Result<TcpStream> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    stream.set_nodelay(true)?;
+
+    stream.write_all(&connect_packet(options)?)?;
+
+    let mut connack = [0; 4];
+    stream.read_exact(&mut connack)?;
+    match connack {
+        [packet::CONNACK, 2, _, 0] => Ok(stream),
+        [packet::CONNACK, 2, _, code] => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("the broker refused the connection (return code {code})"),
+        )),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the broker didn't answer with a CONNACK",
+        )),
+    }
+}
+
+/// Builds a CONNECT packet, with an `offline` will on the status topic.
+fn connect_packet(options: &MqttOptions) -> io::Result<Vec<u8>> {
+    let mut flags = 0x02 | 0x04 | 0x20; // clean session, will, retain the will
+    match (&options.username, &options.password) {
+        (Some(_), Some(_)) => flags |= 0x80 | 0x40,
+        (Some(_), None) => flags |= 0x80,
+        (None, None) => (),
+        // brokers hang up on this (see MQTT 3.1.1, section 3.1.2.9)
+        (None, Some(_)) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "MQTT needs a username to go with the password",
+            ))
+        }
+    }
+
+    let mut body = Vec::new();
+    push_str(&mut body, "MQTT")?;
+    body.push(4); // 3.1.1
+    body.push(flags);
+    body.extend_from_slice(
+        &(options.keep_alive.as_secs().min(u16::MAX as u64) as u16).to_be_bytes(),
+    );
+
+    push_str(&mut body, &options.client_id)?;
+    push_str(&mut body, &options.status_topic())?;
+    push_str(&mut body, "offline")?;
+    if let Some(username) = &options.username {
+        push_str(&mut body, username)?;
+    }
+    if let Some(password) = &options.password {
+        push_str(&mut body, password)?;
+    }
+
+    frame(packet::CONNECT, &body)
+}
+
+/// Puts a fixed header in front of a packet body.
+///
+/// The remaining length only goes up to four bytes, so bodies can't be any
+/// bigger than 268,435,455 bytes.
+fn frame(header: u8, body: &[u8]) -> io::Result<Vec<u8>> {
+    if body.len() > 0x0FFF_FFFF {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("a {} byte packet is too big for MQTT", body.len()),
+        ));
+    }
+
+    let mut packet = vec![header];
+
+    // the remaining length is seven bits at a time, low bits first
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+
+    packet.extend_from_slice(body);
+    Ok(packet)
+}
+
+/// Appends a length-prefixed string, which can't be any longer than 65,535
+/// bytes.
+fn push_str(buf: &mut Vec<u8>, s: &str) -> io::Result<()> {
+    let len = u16::try_from(s.len()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("a {} byte string is too long for MQTT", s.len()),
+        )
+    })?;
+
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+/// Makes a name safe for a Home Assistant discovery topic.
+fn slug(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '_' | '-' => c,
+            'A'..='Z' => c.to_ascii_lowercase(),
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        net::{SocketAddr, TcpListener},
+        thread::JoinHandle,
+    };
+
+    use super::*;
+
+    /// Something the stand-in broker heard.
+    #[derive(Debug, PartialEq)]
+    pub(crate) enum Heard {
+        Connect {
+            client_id: String,
+            will: String,
+        },
+        Publish {
+            topic: String,
+            payload: String,
+            retain: bool,
+        },
+        Ping,
+        Disconnect,
+    }
+
+    /// Reads a length-prefixed string from a packet body.
+    fn take_str(body: &mut &[u8]) -> String {
+        let len = u16::from_be_bytes([body[0], body[1]]) as usize;
+        let s = String::from_utf8(body[2..2 + len].to_vec()).unwrap();
+        *body = &body[2 + len..];
+        s
+    }
+
+    /// A pretend broker that accepts one client, answers its CONNECT with
+    /// `return_code`, and writes down everything it's sent.
+    pub(crate) fn broker(return_code: u8) -> (SocketAddr, JoinHandle<Vec<Heard>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let thread = std::thread::spawn(move || answer(&listener, return_code, usize::MAX));
+        (addr, thread)
+    }
+
+    /// Accepts one client on `listener` and writes down what it sends, like
+    /// [`broker`], hanging up after hearing `hang_up_after` packets.
+    fn answer(listener: &TcpListener, return_code: u8, hang_up_after: usize) -> Vec<Heard> {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut heard = Vec::new();
+
+        while heard.len() < hang_up_after {
+            let mut header = [0];
+            if stream.read_exact(&mut header).is_err() {
+                return heard;
+            }
+
+            let (mut len, mut shift) = (0, 0);
+            loop {
+                let mut byte = [0];
+                stream.read_exact(&mut byte).unwrap();
+                len |= ((byte[0] & 0x7F) as usize) << shift;
+                shift += 7;
+                if byte[0] & 0x80 == 0 {
+                    break;
+                }
+            }
+
+            let mut body = vec![0; len];
+            stream.read_exact(&mut body).unwrap();
+            let mut body = body.as_slice();
+
+            match header[0] & 0xF0 {
+                packet::CONNECT => {
+                    assert_eq!(take_str(&mut body), "MQTT");
+                    body = &body[4..];
+                    let client_id = take_str(&mut body);
+                    let will = take_str(&mut body);
+                    heard.push(Heard::Connect { client_id, will });
+                    stream
+                        .write_all(&[packet::CONNACK, 2, 0, return_code])
+                        .unwrap();
+                    if return_code != 0 {
+                        return heard;
+                    }
+                }
+                packet::PUBLISH => {
+                    let topic = take_str(&mut body);
+                    heard.push(Heard::Publish {
+                        topic,
+                        payload: String::from_utf8(body.to_vec()).unwrap(),
+                        retain: header[0] & 1 == 1,
+                    });
+                }
+                packet::PINGREQ => {
+                    heard.push(Heard::Ping);
+                    stream.write_all(&[packet::PINGRESP, 0]).unwrap();
+                }
+                packet::DISCONNECT => {
+                    heard.push(Heard::Disconnect);
+                    return heard;
+                }
+                other => panic!("unexpected packet type {other:#04x}"),
+            }
+        }
+
+        heard
+    }
+
+    fn publish(topic: &str, payload: &str, retain: bool) -> Heard {
+        Heard::Publish {
+            topic: topic.into(),
+            payload: payload.into(),
+            retain,
+        }
+    }
+
+    #[test]
+    fn publishes_readings() {
+        let (addr, broker) = broker(0);
+        let mut options = MqttOptions::new("shop");
+        options.discovery_prefix = None;
+
+        let mut mqtt = Publisher::connect(addr, options).unwrap();
+        mqtt.publish("furnace", &Reading::try_from(0x0320)).unwrap();
+        mqtt.publish("furnace", &Reading::try_from(0x0004)).unwrap();
+        mqtt.publish("furnace", &Err(Error::ReceivedNothing))
+            .unwrap();
+        mqtt.ping().unwrap();
+        drop(mqtt);
+
+        assert_eq!(
+            broker.join().unwrap(),
+            [
+                Heard::Connect {
+                    client_id: "shop".into(),
+                    will: "max6675/shop/status".into()
+                },
+                publish("max6675/shop/status", "online", true),
+                publish("max6675/shop/furnace/open", "OFF", false),
+                publish("max6675/shop/furnace/temperature", "25", false),
+                publish("max6675/shop/furnace/open", "ON", false),
+                publish(
+                    "max6675/shop/furnace/error",
+                    &Error::ReceivedNothing.to_string(),
+                    false
+                ),
+                Heard::Ping,
+                publish("max6675/shop/status", "offline", true),
+                Heard::Disconnect,
+            ]
+        );
+    }
+
+    #[test]
+    fn announces_each_sensor_once() {
+        let (addr, broker) = broker(0);
+        let mut mqtt = Publisher::connect(addr, MqttOptions::new("Shop Floor")).unwrap();
+        for _ in 0..2 {
+            mqtt.publish("Furnace #1", &Reading::try_from(0x0320))
+                .unwrap();
+        }
+        drop(mqtt);
+
+        let heard = broker.join().unwrap();
+        let configs: Vec<_> = heard
+            .iter()
+            .filter_map(|heard| match heard {
+                Heard::Publish {
+                    topic,
+                    payload,
+                    retain: true,
+                } if topic.ends_with("/config") => Some((topic.as_str(), payload.as_str())),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(configs.len(), 2);
+        assert_eq!(
+            configs[0].0,
+            "homeassistant/sensor/shop_floor/furnace__1_temperature/config"
+        );
+        assert_eq!(
+            configs[1].0,
+            "homeassistant/binary_sensor/shop_floor/furnace__1_open/config"
+        );
+
+        let temperature: serde_json::Value = serde_json::from_str(configs[0].1).unwrap();
+        assert_eq!(temperature["name"], "Furnace #1 temperature");
+        assert_eq!(
+            temperature["state_topic"],
+            "max6675/shop_floor/furnace__1/temperature"
+        );
+        assert_eq!(
+            temperature["availability_topic"],
+            "max6675/shop_floor/status"
+        );
+        assert_eq!(temperature["device_class"], "temperature");
+        assert_eq!(temperature["unit_of_measurement"], "°C");
+    }
+
+    #[test]
+    fn pings_when_idle() {
+        let (addr, broker) = broker(0);
+        let mut options = MqttOptions::new("shop");
+        options.keep_alive = Duration::from_millis(100);
+
+        let mut mqtt = Publisher::connect(addr, options).unwrap();
+        // we only just said we're `online`, so there's no need yet
+        mqtt.ping_if_idle().unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+        mqtt.ping_if_idle().unwrap();
+        mqtt.ping_if_idle().unwrap();
+        drop(mqtt);
+
+        assert_eq!(
+            broker.join().unwrap(),
+            [
+                Heard::Connect {
+                    client_id: "shop".into(),
+                    will: "max6675/shop/status".into()
+                },
+                publish("max6675/shop/status", "online", true),
+                Heard::Ping,
+                publish("max6675/shop/status", "offline", true),
+                Heard::Disconnect,
+            ]
+        );
+    }
+
+    #[test]
+    fn reconnects_when_the_broker_goes_away() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (hung_up, on_hang_up) = std::sync::mpsc::channel();
+
+        let broker = std::thread::spawn(move || {
+            // the CONNECT, `online`, two discovery configs, `open`, and
+            // `temperature`
+            let first = answer(&listener, 0, 6);
+            hung_up.send(()).unwrap();
+            (first, answer(&listener, 0, usize::MAX))
+        });
+
+        let mut mqtt = Publisher::connect(addr, MqttOptions::new("shop")).unwrap();
+        mqtt.publish("furnace", &Reading::try_from(0x0320)).unwrap();
+        on_hang_up.recv().unwrap();
+        mqtt.publish("furnace", &Reading::try_from(0x0320)).unwrap();
+        drop(mqtt);
+
+        let (first, second) = broker.join().unwrap();
+        assert_eq!(first.len(), 6);
+
+        // the new connection hears everything over again, discovery and all
+        assert_eq!(second.len(), 8, "{second:?}");
+        assert_eq!(
+            second[..2],
+            [
+                Heard::Connect {
+                    client_id: "shop".into(),
+                    will: "max6675/shop/status".into()
+                },
+                publish("max6675/shop/status", "online", true),
+            ]
+        );
+        assert!(matches!(&second[2], Heard::Publish { topic, .. } if topic.ends_with("/config")));
+        assert_eq!(
+            second[5],
+            publish("max6675/shop/furnace/temperature", "25", false)
+        );
+    }
+
+    #[test]
+    fn refused_connections_are_an_error() {
+        let (addr, broker) = broker(5);
+        let e = Publisher::connect(addr, MqttOptions::new("shop")).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::ConnectionRefused);
+        broker.join().unwrap();
+    }
+
+    #[test]
+    fn encodes_long_remaining_lengths() {
+        let packet = frame(packet::PUBLISH, &[0; 321]).unwrap();
+        assert_eq!(&packet[..3], [packet::PUBLISH, 0xC1, 0x02]);
+        assert_eq!(packet.len(), 3 + 321);
+    }
+
+    #[test]
+    fn refuses_to_build_broken_packets() {
+        let mut options = MqttOptions::new("shop");
+        options.password = Some("hunter2".into());
+        let e = connect_packet(&options).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+
+        options.username = Some("shop".into());
+        let packet = connect_packet(&options).unwrap();
+        assert_eq!(packet[9], 0x02 | 0x04 | 0x20 | 0x80 | 0x40);
+
+        let e = push_str(&mut Vec::new(), &"a".repeat(65_536)).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn long_topics_are_an_error() {
+        let (addr, broker) = broker(0);
+        let mut options = MqttOptions::new("shop");
+        options.discovery_prefix = None;
+
+        let mut mqtt = Publisher::connect(addr, options).unwrap();
+        let e = mqtt
+            .publish_celsius(&"a".repeat(65_536), &Ok(20.0))
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        drop(mqtt);
+
+        // nothing half-baked went out, and we didn't reconnect over it
+        assert_eq!(
+            broker.join().unwrap(),
+            [
+                Heard::Connect {
+                    client_id: "shop".into(),
+                    will: "max6675/shop/status".into()
+                },
+                publish("max6675/shop/status", "online", true),
+                publish("max6675/shop/status", "offline", true),
+                Heard::Disconnect,
+            ]
+        );
+    }
+}
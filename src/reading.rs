@@ -0,0 +1,158 @@
+//! # reading
+//!
+//! A decoded 16-bit word from the MAX6675.
+//!
+//! Here's what each bit means (see MAX6675 datasheet, p. 5):
+//!
+//! | Bit     | Meaning                                        |
+//! |---------|------------------------------------------------|
+//! | D15     | Dummy sign bit. Always 0.                      |
+//! | D14-D3  | 12-bit temperature, in quarter degrees Celsius |
+//! | D2      | High when the thermocouple input is open       |
+//! | D1      | Device ID. Always 0.                           |
+//! | D0      | Three-state. Could be anything!                |
+
+use crate::{Error, Temperature};
+
+const SIGN_BIT: u16 = 1 << 15;
+const OPEN_BIT: u16 = 1 << 2;
+const DEVICE_ID_BIT: u16 = 1 << 1;
+const THREE_STATE_BIT: u16 = 1 << 0;
+
+/// One decoded word from the MAX6675.
+///
+/// ## Example
+///
+/// ```
+///
+/// use linux_max6675::{Error, Reading};
+///
+/// let reading = Reading::from_raw(0b0000_0000_1010_1000).unwrap();
+/// assert_eq!(reading.temperature_bits(), 21);
+/// assert_eq!(reading.celsius(), 5.25);
+/// assert!(!reading.is_open());
+///
+/// // D15 should never be set by a real MAX6675
+/// assert_eq!(Reading::from_raw(0x8000), Err(Error::InvalidFrame { raw: 0x8000 }));
+///
+/// ```
+///
+/// With the `serde` feature, a reading is serialized as its raw word, and
+/// checked with [`Reading::from_raw`] when it's deserialized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "u16", into = "u16"))]
+pub struct Reading {
+    raw: u16,
+}
+
+impl Reading {
+    /// Decodes a raw word, checking that its fixed bits make sense.
+    ///
+    /// Fails with [`Error::InvalidFrame`] if the dummy sign bit (D15) or the
+    /// device ID bit (D1) is set, since a real MAX6675 never does that.
+    pub fn from_raw(raw: u16) -> Result<Self, Error> {
+        let reading = Self { raw };
+
+        if reading.sign_bit() || reading.device_id() {
+            return Err(Error::InvalidFrame { raw });
+        }
+
+        Ok(reading)
+    }
+
+    /// Decodes a raw word without checking it at all.
+    pub fn from_raw_unchecked(raw: u16) -> Self {
+        Self { raw }
+    }
+
+    /// The raw word, just as it came off the wire.
+    pub fn raw(&self) -> u16 {
+        self.raw
+    }
+
+    /// The dummy sign bit, D15.
+    pub fn sign_bit(&self) -> bool {
+        self.raw & SIGN_BIT != 0
+    }
+
+    /// The 12-bit temperature from D14-D3, in quarter degrees Celsius.
+    pub fn temperature_bits(&self) -> u16 {
+        (self.raw >> 3) & 0x0FFF
+    }
+
+    /// The exact temperature.
+    ///
+    /// This doesn't care whether the thermocouple is open, so check
+    /// [`Reading::is_open`] first!
+    pub fn temperature(&self) -> Temperature {
+        crate::parse_temperature(self.raw)
+    }
+
+    /// The temperature in Celsius. See [`Reading::temperature`].
+    pub fn celsius(&self) -> f64 {
+        self.temperature().celsius()
+    }
+
+    /// Whether the thermocouple input is open (D2).
+    pub fn is_open(&self) -> bool {
+        self.raw & OPEN_BIT != 0
+    }
+
+    /// The device ID bit, D1.
+    pub fn device_id(&self) -> bool {
+        self.raw & DEVICE_ID_BIT != 0
+    }
+
+    /// The three-state bit, D0.
+    pub fn three_state(&self) -> bool {
+        self.raw & THREE_STATE_BIT != 0
+    }
+}
+
+impl TryFrom<u16> for Reading {
+    type Error = Error;
+
+    fn try_from(raw: u16) -> Result<Self, Error> {
+        Self::from_raw(raw)
+    }
+}
+
+impl From<Reading> for u16 {
+    fn from(reading: Reading) -> Self {
+        reading.raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_every_bit() {
+        let reading = Reading::from_raw(0b0111_1111_1111_1101).unwrap();
+
+        assert!(!reading.sign_bit());
+        assert_eq!(reading.temperature_bits(), 0x0FFF);
+        assert_eq!(reading.celsius(), 1023.75);
+        assert!(reading.is_open());
+        assert!(!reading.device_id());
+        assert!(reading.three_state());
+    }
+
+    #[test]
+    fn rejects_impossible_frames() {
+        for raw in [0x8000, 0x0002, 0xFFFF] {
+            assert_eq!(Reading::from_raw(raw), Err(Error::InvalidFrame { raw }));
+        }
+
+        // ...but we'll still decode them if you insist
+        assert!(Reading::from_raw_unchecked(0xFFFF).sign_bit());
+    }
+
+    #[test]
+    fn three_state_bit_is_fine_either_way() {
+        assert!(Reading::from_raw(0x0000).is_ok());
+        assert!(Reading::from_raw(0x0001).is_ok());
+    }
+}
@@ -0,0 +1,386 @@
+//! # sampler
+//!
+//! Reads a sensor in the background, so everyone else can just ask.
+//!
+//! Reading a [`Max6675`](crate::Max6675) takes `&mut`, so only one part of
+//! your program can own it. [`Sampler`] moves the sensor onto its own thread,
+//! reads it on a fixed schedule, and keeps the latest [`Sample`] where any
+//! number of [`Handle`]s can grab it. Handles are cheap to clone, and never
+//! wait on the sampler or each other.
+//!
+//! The thread stops when the `Sampler` is dropped. Handles keep working after
+//! that, but the sample they see won't change anymore.
+//!
+//! ## Example
+//!
+//! ```no_run
+//!
+//! use linux_max6675::{sampler::Sampler, Max6675};
+//! use std::time::Duration;
+//!
+//! let max = Max6675::new("/dev/spidev0.0").unwrap();
+//! let sampler = Sampler::new(max, Duration::from_millis(250));
+//!
+//! let handle = sampler.handle();
+//! std::thread::spawn(move || loop {
+//!     if let Some(sample) = handle.latest() {
+//!         println!("{:?}, {:?} ago", sample.value, sample.age());
+//!     }
+//!     std::thread::sleep(Duration::from_secs(1));
+//! });
+//!
+//! ```
+
+use std::{
+    fmt, ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{Error, Sensor};
+
+/// One reading, and when it was taken.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    /// When the reading was taken.
+    pub timestamp: SystemTime,
+    /// The same moment, on a clock that doesn't jump around.
+    pub taken_at: Instant,
+    /// The temperature in ° C, or what went wrong.
+    pub value: Result<f64, Error>,
+}
+
+impl Sample {
+    /// How long ago the reading was taken.
+    pub fn age(&self) -> Duration {
+        self.taken_at.elapsed()
+    }
+}
+
+/// What the sampler's thread shares with the handles.
+///
+/// Handles check in before looking at `latest`, on one of two counters
+/// picked by `epoch`. To replace a sample, the sampler swaps in the new one,
+/// bumps `epoch` so everyone after that checks in on the other counter, and
+/// waits for the old counter to drain. Only readers who were already
+/// mid-clone can hold it up, so the old sample gets freed right away, and
+/// nothing piles up no matter how busy the handles are.
+#[derive(Debug)]
+struct Shared {
+    /// The latest sample, or null before the first one.
+    latest: AtomicPtr<Sample>,
+    epoch: AtomicUsize,
+    /// How many handles are looking at `latest`, for even and odd epochs.
+    readers: [AtomicUsize; 2],
+    stop: AtomicBool,
+}
+
+impl Shared {
+    fn load(&self) -> Option<Sample> {
+        let epoch = loop {
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            self.readers[epoch % 2].fetch_add(1, Ordering::SeqCst);
+            if self.epoch.load(Ordering::SeqCst) == epoch {
+                break epoch;
+            }
+
+            // the sampler moved on while we were checking in, so it might
+            // not wait for us. try again on the new epoch
+            self.readers[epoch % 2].fetch_sub(1, Ordering::SeqCst);
+        };
+
+        // SAFETY: `latest` is either null or came from `Box::into_raw`, and
+        // the sampler won't free it until we've checked out
+        let sample = unsafe { self.latest.load(Ordering::SeqCst).as_ref() }.cloned();
+        self.readers[epoch % 2].fetch_sub(1, Ordering::SeqCst);
+        sample
+    }
+
+    /// Swaps in a new sample, and frees the old one once nobody can be
+    /// looking at it. Only the sampler's thread calls this.
+    fn publish(&self, sample: Sample) {
+        let new = Box::into_raw(Box::new(sample));
+        let old = self.latest.swap(new, Ordering::SeqCst);
+
+        // anyone checking in after this sees `new`, so only the readers
+        // counted under the old epoch could still have `old`
+        let epoch = self.epoch.fetch_add(1, Ordering::SeqCst);
+        while self.readers[epoch % 2].load(Ordering::SeqCst) != 0 {
+            thread::yield_now();
+        }
+
+        if !old.is_null() {
+            // SAFETY: it came from `Box::into_raw`, and nobody can see it
+            // anymore
+            drop(unsafe { Box::from_raw(old) });
+        }
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        let latest = *self.latest.get_mut();
+        if !latest.is_null() {
+            // SAFETY: it came from `Box::into_raw`, and nobody else can see
+            // it anymore
+            drop(unsafe { Box::from_raw(latest) });
+        }
+    }
+}
+
+/// A cheap, clonable view of a [`Sampler`]'s latest [`Sample`].
+#[derive(Clone)]
+pub struct Handle {
+    shared: Arc<Shared>,
+}
+
+impl fmt::Debug for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("latest", &self.latest())
+            .finish()
+    }
+}
+
+impl Handle {
+    /// The latest sample, or `None` if the sensor hasn't been read yet.
+    ///
+    /// This never blocks.
+    pub fn latest(&self) -> Option<Sample> {
+        self.shared.load()
+    }
+
+    /// Whether the sampler is still taking new samples.
+    pub fn is_running(&self) -> bool {
+        !self.shared.stop.load(Ordering::Relaxed)
+    }
+}
+
+/// Reads a [`Sensor`] every `interval` on a background thread.
+///
+/// Readings happen on a fixed schedule, like the daemon's: if one runs late,
+/// the next is due `interval` after it was supposed to be, and if the sampler
+/// falls a whole interval behind, it starts fresh instead of catching up.
+#[derive(Debug)]
+pub struct Sampler<S> {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<S>>,
+}
+
+impl<S> Sampler<S>
+where
+    S: Sensor + Send + 'static,
+{
+    /// Moves `sensor` onto a new thread and starts reading it every
+    /// `interval`, starting right away.
+    ///
+    /// With an interval of zero, it reads as fast as the sensor allows. For a
+    /// [`Max6675`](crate::Max6675), that's once per conversion time.
+    pub fn new(sensor: S, interval: Duration) -> Self {
+        let shared = Arc::new(Shared {
+            latest: AtomicPtr::new(ptr::null_mut()),
+            epoch: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            stop: AtomicBool::new(false),
+        });
+
+        let publisher = Arc::clone(&shared);
+        let thread = thread::Builder::new()
+            .name("max6675-sampler".to_string())
+            .spawn(move || run(sensor, interval, publisher))
+            .expect("Couldn't spawn the sampler's thread.");
+
+        Self {
+            shared,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl<S> Sampler<S> {
+    /// Gets a handle to the latest sample, for sharing around.
+    pub fn handle(&self) -> Handle {
+        Handle {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// The latest sample, or `None` if the sensor hasn't been read yet.
+    pub fn latest(&self) -> Option<Sample> {
+        self.shared.load()
+    }
+
+    /// Stops the thread and gives the sensor back.
+    ///
+    /// If a reading is in progress, this waits for it to finish.
+    ///
+    /// # Panics
+    ///
+    /// If the sensor panicked on the sampler's thread, this panics too.
+    pub fn into_inner(mut self) -> S {
+        match self.stop() {
+            Some(Ok(sensor)) => sensor,
+            Some(Err(panic)) => std::panic::resume_unwind(panic),
+            None => unreachable!("the thread is only stopped once"),
+        }
+    }
+
+    /// Stops the thread, if it hasn't been already.
+    fn stop(&mut self) -> Option<thread::Result<S>> {
+        let thread = self.thread.take()?;
+        self.shared.stop.store(true, Ordering::Relaxed);
+        thread.thread().unpark();
+        Some(thread.join())
+    }
+}
+
+impl<S> Drop for Sampler<S> {
+    fn drop(&mut self) {
+        // if the sensor panicked, there's nothing we can do about it now
+        let _ = self.stop();
+    }
+}
+
+/// Reads `sensor` until we're told to stop.
+fn run<S: Sensor>(mut sensor: S, interval: Duration, shared: Arc<Shared>) -> S {
+    let mut due = Instant::now();
+
+    while !shared.stop.load(Ordering::Relaxed) {
+        let now = Instant::now();
+        if due > now {
+            // dropping the sampler unparks us, so this doesn't hold it up
+            thread::park_timeout(due - now);
+            continue;
+        }
+
+        let value = sensor.read_celsius();
+        shared.publish(Sample {
+            timestamp: SystemTime::now(),
+            taken_at: Instant::now(),
+            value,
+        });
+
+        // if we've fallen behind, don't try to catch up all at once
+        due += interval;
+        let now = Instant::now();
+        if due < now {
+            due = now;
+        }
+    }
+
+    sensor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::mock::MockMax6675, Max6675};
+
+    fn mock(celsius: &[f64]) -> Max6675<MockMax6675> {
+        let mut spi = MockMax6675::new();
+        for &c in celsius {
+            spi.push_celsius(c);
+        }
+        let mut max = Max6675::from_spi(spi);
+        max.set_conversion_time(Duration::ZERO);
+        max
+    }
+
+    /// Waits for the handle to see a sample that passes `check`.
+    fn wait_for(handle: &Handle, check: impl Fn(&Sample) -> bool) -> Sample {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(sample) = handle.latest().filter(&check) {
+                return sample;
+            }
+            assert!(Instant::now() < deadline, "never saw the sample we wanted");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn keeps_the_latest_sample() {
+        let sampler = Sampler::new(mock(&[20.0, 21.0]), Duration::from_millis(5));
+        let handle = sampler.handle();
+
+        let first = wait_for(&handle, |_| true);
+        assert_eq!(first.value, Ok(20.0));
+
+        // once the mock runs dry, the errors come through too
+        let last = wait_for(&handle, |s| s.value.is_err());
+        assert_eq!(last.value, Err(Error::ReceivedNothing));
+        assert!(last.taken_at > first.taken_at);
+
+        // and every clone sees the same thing
+        assert_eq!(handle.clone().latest(), sampler.latest());
+    }
+
+    #[test]
+    fn stops_when_dropped() {
+        // a long interval, so the thread is asleep when we drop it
+        let sampler = Sampler::new(mock(&[20.0]), Duration::from_secs(3600));
+        let handle = sampler.handle();
+        wait_for(&handle, |_| true);
+        assert!(handle.is_running());
+
+        let start = Instant::now();
+        drop(sampler);
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // the handle outlives it, with the last sample it took
+        assert!(!handle.is_running());
+        assert_eq!(handle.latest().unwrap().value, Ok(20.0));
+    }
+
+    #[test]
+    fn gives_the_sensor_back() {
+        let sampler = Sampler::new(mock(&[20.0]), Duration::from_secs(3600));
+        wait_for(&sampler.handle(), |_| true);
+
+        let max = sampler.into_inner();
+        assert_eq!(max.into_inner().reads(), 1);
+    }
+
+    #[test]
+    fn readers_dont_get_in_the_way() {
+        let sampler = Sampler::new(mock(&[]), Duration::ZERO);
+
+        // readers that never let up, right through the sampler stopping
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let handle = sampler.handle();
+                thread::spawn(move || {
+                    while handle.is_running() {
+                        if let Some(sample) = handle.latest() {
+                            assert_eq!(sample.value, Err(Error::ReceivedNothing));
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        // each new sample means the old one was freed, so the sampler keeps
+        // up (and doesn't pile up old samples) with readers all over it
+        let handle = sampler.handle();
+        let mut last = wait_for(&handle, |_| true);
+        for _ in 0..100 {
+            last = wait_for(&handle, |s| s.taken_at > last.taken_at);
+        }
+
+        let start = Instant::now();
+        drop(sampler);
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        // and everyone checked out again
+        let shared = &handle.shared;
+        assert!(shared.readers.iter().all(|r| r.load(Ordering::SeqCst) == 0));
+    }
+}
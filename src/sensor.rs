@@ -0,0 +1,49 @@
+//! # sensor
+//!
+//! Anything you can read a temperature from.
+
+use embedded_hal::spi::SpiDevice;
+
+use crate::{
+    filter::{Filter, Filtered},
+    Error, Max6675,
+};
+
+/// Something that reads temperatures, like a [`Max6675`], or a wrapper
+/// around one.
+///
+/// Wrappers implement this too, so they stack however you like.
+pub trait Sensor {
+    /// Tries to read the temperature in Celsius.
+    fn read_celsius(&mut self) -> Result<f64, Error>;
+
+    /// Runs every successful reading through a [`Filter`].
+    fn filtered<F: Filter>(self, filter: F) -> Filtered<Self, F>
+    where
+        Self: Sized,
+    {
+        Filtered::new(self, filter)
+    }
+}
+
+impl<SPI> Sensor for Max6675<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    fn read_celsius(&mut self) -> Result<f64, Error> {
+        Max6675::read_celsius(self)
+    }
+}
+
+impl<S: Sensor + ?Sized> Sensor for &mut S {
+    fn read_celsius(&mut self) -> Result<f64, Error> {
+        (**self).read_celsius()
+    }
+}
+
+impl<S: Sensor + ?Sized> Sensor for Box<S> {
+    fn read_celsius(&mut self) -> Result<f64, Error> {
+        (**self).read_celsius()
+    }
+}
@@ -0,0 +1,89 @@
+//! # temperature
+//!
+//! An exact, fixed-point temperature.
+//!
+//! The MAX6675 reports temperatures in quarter degrees, so we store them that
+//! way too. No floating point surprises until you ask for them!
+
+use std::fmt;
+
+/// A temperature, stored exactly as a whole number of quarter degrees Celsius.
+///
+/// ## Example
+///
+/// ```
+///
+/// use linux_max6675::Temperature;
+///
+/// let temp = Temperature::from_quarters(401);
+///
+/// assert_eq!(temp.celsius(), 100.25);
+/// assert_eq!(temp.fahrenheit(), 212.45);
+/// assert_eq!(temp.to_string(), "100.25° C");
+///
+/// ```
+///
+/// With the `serde` feature, a temperature is serialized as its number of
+/// quarter degrees, so nothing gets lost along the way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Temperature {
+    quarters: i16,
+}
+
+impl Temperature {
+    /// Zero degrees Celsius.
+    pub const ZERO: Temperature = Temperature::from_quarters(0);
+
+    /// Creates a temperature from a number of quarter degrees Celsius.
+    pub const fn from_quarters(quarters: i16) -> Self {
+        Self { quarters }
+    }
+
+    /// The temperature as a number of quarter degrees Celsius.
+    pub const fn quarters(self) -> i16 {
+        self.quarters
+    }
+
+    /// The temperature in Celsius.
+    pub fn celsius(self) -> f64 {
+        f64::from(self.quarters) / 4.0
+    }
+
+    /// The temperature in Celsius, as an `f32`.
+    ///
+    /// Every possible temperature fits in an `f32` exactly, so this is lossless.
+    pub fn celsius_f32(self) -> f32 {
+        f32::from(self.quarters) / 4.0
+    }
+
+    /// The temperature in Fahrenheit.
+    pub fn fahrenheit(self) -> f64 {
+        // (q / 4) * (9 / 5) + 32 = (9q + 640) / 20, with just one rounding step
+        f64::from(i32::from(self.quarters) * 9 + 640) / 20.0
+    }
+
+    /// The temperature in Kelvin.
+    pub fn kelvin(self) -> f64 {
+        self.celsius() + 273.15
+    }
+}
+
+impl From<Temperature> for f64 {
+    fn from(temp: Temperature) -> Self {
+        temp.celsius()
+    }
+}
+
+impl From<Temperature> for f32 {
+    fn from(temp: Temperature) -> Self {
+        temp.celsius_f32()
+    }
+}
+
+impl fmt::Display for Temperature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}° C", self.celsius())
+    }
+}
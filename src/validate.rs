@@ -0,0 +1,291 @@
+//! # validate
+//!
+//! Throws out readings that can't be right.
+//!
+//! Noise on SO can flip a few bits and turn 21° into 533°. [`Validator`]
+//! checks each reading against a range and a maximum rate of change, and
+//! re-reads when one looks wrong.
+//!
+//! ## Example
+//!
+//! ```no_run
+//!
+//! use linux_max6675::{validate::Validator, Max6675};
+//!
+//! let mut max = Validator::new(Max6675::new("/dev/spidev0.0").unwrap());
+//! max.set_range(0.0..=300.0);
+//! max.set_max_slew(Some(20.0)); // ° C per second
+//! max.set_retries(3);
+//!
+//! loop {
+//!     println!("{}° C", max.read_celsius().unwrap());
+//! }
+//!
+//! ```
+
+use std::{ops::RangeInclusive, time::Instant};
+
+use embedded_hal::spi::SpiDevice;
+
+use crate::{Error, Max6675, Reading, Sensor};
+
+/// A [`Max6675`] that rejects implausible readings.
+///
+/// A reading is implausible if it's outside the range you set, or if it's
+/// changed faster than the maximum slew rate since the last good reading.
+/// When that happens, the validator reads again, up to `retries` more times.
+///
+/// Sometimes the temperature really does jump, like when you plug a
+/// thermocouple back in. If two re-reads in a row agree with each other, the
+/// validator believes them. Otherwise, you'll get an [`Error::Implausible`]
+/// with the last raw word it saw.
+///
+/// Keep in mind that each re-read has to wait for the chip's conversion time
+/// (whatever the driver's [`ConversionPolicy`](crate::ConversionPolicy)), so
+/// retries can take a while.
+///
+/// Readings are checked before the driver's calibration is applied, so keep
+/// your range in terms of what the chip says.
+#[derive(Debug)]
+pub struct Validator<SPI> {
+    max: Max6675<SPI>,
+    range: RangeInclusive<f64>,
+    /// In ° C per second.
+    max_slew: Option<f64>,
+    retries: usize,
+    /// The last good reading, in ° C, and when we got it.
+    last: Option<(f64, Instant)>,
+}
+
+impl<SPI> Validator<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    /// The range a MAX6675 can actually report.
+    pub const FULL_RANGE: RangeInclusive<f64> = 0.0..=1023.75;
+
+    /// Wraps a driver. To start with, anything in the chip's range goes, and
+    /// there's no slew limit. Bad readings are retried twice.
+    pub fn new(max: Max6675<SPI>) -> Self {
+        Self {
+            max,
+            range: Self::FULL_RANGE,
+            max_slew: None,
+            retries: 2,
+            last: None,
+        }
+    }
+
+    /// Rejects readings outside of `range`, in ° C.
+    pub fn set_range(&mut self, range: RangeInclusive<f64>) {
+        self.range = range;
+    }
+
+    /// Rejects readings that change faster than `max_slew` ° C per second.
+    /// `None` turns this off.
+    pub fn set_max_slew(&mut self, max_slew: Option<f64>) {
+        self.max_slew = max_slew;
+    }
+
+    /// How many more times to read after an implausible reading.
+    pub fn set_retries(&mut self, retries: usize) {
+        self.retries = retries;
+    }
+
+    /// Forgets the last good reading, so the next one only has to be in
+    /// range.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// The driver underneath.
+    pub fn inner_mut(&mut self) -> &mut Max6675<SPI> {
+        &mut self.max
+    }
+
+    /// Gives back the driver.
+    pub fn into_inner(self) -> Max6675<SPI> {
+        self.max
+    }
+
+    /// Whether `b` is close enough to `a` for the time between them.
+    fn within_slew(&self, (a, a_at): (f64, Instant), (b, b_at): (f64, Instant)) -> bool {
+        let Some(max_slew) = self.max_slew else {
+            return true;
+        };
+
+        let elapsed = b_at.saturating_duration_since(a_at).as_secs_f64();
+        (b - a).abs() <= max_slew * elapsed
+    }
+
+    /// Reads frames until one looks plausible.
+    ///
+    /// Open circuits aren't retried, since there's no temperature to check.
+    pub fn read_frame(&mut self) -> Result<Reading, Error> {
+        let mut rejected: Option<(f64, Instant)> = None;
+        let mut last_raw = 0;
+
+        for attempt in 0..=self.retries {
+            // re-reads need a fresh conversion, not a cached frame or a
+            // `NotReady`, so wait it out here
+            if attempt > 0 {
+                std::thread::sleep(self.max.time_until_ready());
+            }
+
+            let reading = self.max.read_frame()?;
+            if reading.is_open() {
+                return Ok(reading);
+            }
+
+            let now = (reading.celsius(), Instant::now());
+            last_raw = reading.raw();
+
+            if !self.range.contains(&now.0) {
+                continue;
+            }
+
+            let plausible = match (self.last, rejected) {
+                (None, _) => true,
+                (Some(last), _) if self.within_slew(last, now) => true,
+                // two re-reads that agree mean it really did change
+                (_, Some(previous)) => self.within_slew(previous, now),
+                _ => false,
+            };
+
+            if plausible {
+                self.last = Some(now);
+                return Ok(reading);
+            }
+
+            rejected = Some(now);
+        }
+
+        Err(Error::Implausible { raw: last_raw })
+    }
+
+    /// Tries to read a plausible temperature in Celsius.
+    pub fn read_celsius(&mut self) -> Result<f64, Error> {
+        let reading = self.read_frame()?;
+        if reading.is_open() {
+            return Err(Error::OpenCircuit);
+        }
+
+        Ok(self.max.corrected_celsius(reading.temperature()))
+    }
+}
+
+impl<SPI> Sensor for Validator<SPI>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+{
+    fn read_celsius(&mut self) -> Result<f64, Error> {
+        Validator::read_celsius(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{backend::mock::MockMax6675, ConversionPolicy};
+
+    fn validator(mock: MockMax6675) -> Validator<MockMax6675> {
+        let mut max = Max6675::from_spi(mock);
+        // a little time between reads, so slew rates make sense
+        max.set_conversion_time(Duration::from_millis(10));
+        Validator::new(max)
+    }
+
+    #[test]
+    fn rejects_out_of_range_readings() {
+        let mut mock = MockMax6675::new();
+        mock.push_profile([900.0, 20.0, 900.0, 900.0, 900.0]);
+
+        let mut max = validator(mock);
+        max.set_range(0.0..=300.0);
+
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(
+            max.read_celsius(),
+            Err(Error::Implausible {
+                raw: MockMax6675::encode_celsius(900.0)
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_spikes() {
+        let mut mock = MockMax6675::new();
+        mock.push_profile([20.0, 500.0, 20.25, 500.0, 21.0, 20.5]);
+
+        let mut max = validator(mock);
+        max.set_max_slew(Some(100.0));
+
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(max.read_celsius(), Ok(20.25));
+        // 500 and 21 don't agree, but 21 is fine next to 20.25
+        assert_eq!(max.read_celsius(), Ok(21.0));
+        assert_eq!(max.read_celsius(), Ok(20.5));
+        assert_eq!(max.into_inner().into_inner().remaining(), 0);
+    }
+
+    #[test]
+    fn retries_take_fresh_conversions() {
+        for policy in [ConversionPolicy::Cached, ConversionPolicy::Error] {
+            let mut mock = MockMax6675::new();
+            mock.push_profile([20.0, 500.0, 20.25]);
+
+            let mut max = validator(mock);
+            max.inner_mut().set_conversion_policy(policy);
+            max.set_max_slew(Some(100.0));
+
+            assert_eq!(max.read_celsius(), Ok(20.0));
+            std::thread::sleep(Duration::from_millis(10));
+            // re-reading the cached 500 would make it look like a real jump
+            assert_eq!(max.read_celsius(), Ok(20.25), "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn believes_consistent_jumps() {
+        let mut mock = MockMax6675::new();
+        mock.push_profile([20.0, 400.0, 400.25]);
+
+        let mut max = validator(mock);
+        max.set_max_slew(Some(100.0));
+
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(max.read_celsius(), Ok(400.25));
+    }
+
+    #[test]
+    fn gives_up_after_retries() {
+        let mut mock = MockMax6675::new();
+        mock.push_profile([20.0, 500.0, 20.0]);
+
+        let mut max = validator(mock);
+        max.set_max_slew(Some(100.0));
+        max.set_retries(0);
+
+        assert_eq!(max.read_celsius(), Ok(20.0));
+        assert_eq!(
+            max.read_celsius(),
+            Err(Error::Implausible {
+                raw: MockMax6675::encode_celsius(500.0)
+            })
+        );
+        assert_eq!(max.read_celsius(), Ok(20.0));
+    }
+
+    #[test]
+    fn open_circuits_pass_through() {
+        let mut mock = MockMax6675::new();
+        mock.push_open_circuit();
+
+        let mut max = validator(mock);
+        assert_eq!(max.read_celsius(), Err(Error::OpenCircuit));
+    }
+}